@@ -1,5 +1,6 @@
 use bevy::{prelude::*, window::PrimaryWindow, winit::WinitWindows};
 use std::io::Cursor;
+use towerish_side_effects::{Replay, ReplayPlayer, ReplaySlot};
 use winit::window::Icon;
 
 fn set_window_icon(
@@ -29,5 +30,15 @@ fn main() {
 
     info!("Starting launcher: Native");
     app.add_startup_system(set_window_icon);
+
+    // `--replay <file>` plays a recorded replay once loading finished
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--replay" {
+            let slot = ReplaySlot(args.next().expect("--replay needs a file"));
+            let replay = Replay::read(&slot).expect("Failed to read replay");
+            app.insert_resource(ReplayPlayer::new(replay));
+        }
+    }
     app.run();
 }
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use crate::{CurrentLevel, GameState, StateUpdateEvent};
+
+/// Money earned at the end of every wave.
+pub const WAVE_INCOME: f32 = 100.0;
+/// Share of the balance paid as interest at the end of every wave.
+pub const INTEREST_RATE: f32 = 0.05;
+
+/// The money of the player in the level that is played. All spending and
+/// earning goes through it and ends up in its ledger.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Bank {
+    balance: f32,
+    /// The running or next wave, starting at 1.
+    wave: i32,
+    ledger: Vec<Transaction>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub source: TransactionSource,
+    /// Negative for spending.
+    pub amount: f32,
+    /// The wave the transaction happened in or before, starting at 1.
+    pub wave: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionSource {
+    Build,
+    Upgrade,
+    Sell,
+    Heal,
+    /// Reward for killing an enemy.
+    Bounty,
+    WaveIncome,
+    Interest,
+}
+
+impl Bank {
+    /// An account with `balance` and an empty ledger before `wave`.
+    pub fn new(balance: f32, wave: i32) -> Self {
+        Self {
+            balance,
+            wave,
+            ledger: vec![],
+        }
+    }
+
+    pub fn balance(&self) -> f32 {
+        self.balance
+    }
+
+    pub fn can_afford(&self, price: f32) -> bool {
+        self.balance >= price
+    }
+
+    /// Pays `price` if the balance covers it, returns whether it did.
+    /// Negative and NaN prices are refused.
+    pub fn try_spend(&mut self, source: TransactionSource, price: f32) -> bool {
+        if price.is_nan() || price < 0.0 || !self.can_afford(price) {
+            return false;
+        }
+        self.record(source, -price);
+        true
+    }
+
+    pub fn earn(&mut self, source: TransactionSource, amount: f32) {
+        self.record(source, amount);
+    }
+
+    /// All transactions since the level started or was loaded, oldest first.
+    pub fn ledger(&self) -> &[Transaction] {
+        &self.ledger
+    }
+
+    fn record(&mut self, source: TransactionSource, amount: f32) {
+        self.balance += amount;
+        self.ledger.push(Transaction {
+            source,
+            amount,
+            wave: self.wave,
+        });
+    }
+}
+
+pub fn bank_plugin(app: &mut App) {
+    app.init_resource::<Bank>()
+        .add_system(open_account.in_schedule(OnExit(GameState::MainMenu)))
+        .add_system(pay_bounties_and_income);
+}
+
+fn open_account(mut bank: ResMut<Bank>, level: Res<CurrentLevel>) {
+    *bank = Bank::new(level.definition.money, 1);
+}
+
+fn pay_bounties_and_income(
+    mut bank: ResMut<Bank>,
+    mut ev_state_update: EventReader<StateUpdateEvent>,
+) {
+    for event in ev_state_update.iter() {
+        match event {
+            StateUpdateEvent::EnemyKilled(bounty) => {
+                bank.earn(TransactionSource::Bounty, *bounty)
+            }
+            StateUpdateEvent::EndWave => {
+                bank.earn(TransactionSource::WaveIncome, WAVE_INCOME);
+                let interest = bank.balance.max(0.0) * INTEREST_RATE;
+                bank.earn(TransactionSource::Interest, interest);
+                bank.wave += 1;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{Enemy, EnemyType, Health, StateUpdateEvent};
+
+/// What kind of damage the projectiles of a tower deal.
+#[derive(
+    Debug, Default, Reflect, Copy, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum DamageType {
+    #[default]
+    Kinetic,
+    Explosive,
+    /// Goes through [`Armor`].
+    Piercing,
+}
+
+/// Damage taken off every hit before [`Resistances`] apply.
+#[derive(Debug, Default, Reflect, Component, Copy, Clone, Deserialize)]
+pub struct Armor(pub f32);
+
+/// Fraction of the damage of each [`DamageType`] that is ignored, negative
+/// values make an enemy take more damage.
+#[derive(Debug, Default, Reflect, Component, Clone, Deserialize)]
+#[serde(default)]
+pub struct Resistances {
+    pub kinetic: f32,
+    pub explosive: f32,
+    pub piercing: f32,
+}
+
+impl Resistances {
+    pub fn get(&self, damage_type: DamageType) -> f32 {
+        match damage_type {
+            DamageType::Kinetic => self.kinetic,
+            DamageType::Explosive => self.explosive,
+            DamageType::Piercing => self.piercing,
+        }
+    }
+}
+
+impl DamageType {
+    /// Health a hit with `force` takes from an enemy, a tenth of the force
+    /// before armor and resistances.
+    pub fn damage(
+        self,
+        force: f32,
+        armor: Armor,
+        resistances: &Resistances,
+    ) -> f32 {
+        let armor = if self == DamageType::Piercing {
+            0.0
+        } else {
+            armor.0
+        };
+        (0.1 * force - armor).max(0.0) * (1.0 - resistances.get(self))
+    }
+}
+
+/// Health an enemy loses, negative amounts heal it.
+pub struct DamageEvent {
+    pub entity: Entity,
+    pub amount: f32,
+    /// The tower the damage is credited to.
+    pub tower: Option<Entity>,
+}
+
+/// Sent exactly once for every enemy that is killed.
+pub struct EnemyDied {
+    pub entity: Entity,
+    pub kind: EnemyType,
+    /// The tower that dealt the final blow.
+    pub killer_tower: Option<Entity>,
+    /// Damage of the final blow beyond the health that was left.
+    pub overkill: f32,
+}
+
+pub fn damage_plugin(app: &mut App) {
+    app.add_event::<DamageEvent>()
+        .add_event::<EnemyDied>()
+        .add_system(apply_damage.in_base_set(CoreSet::PostUpdate));
+}
+
+/// The only place enemies die, after all damage of the frame was dealt.
+fn apply_damage(
+    mut commands: Commands,
+    mut ev_damage: EventReader<DamageEvent>,
+    mut ev_died: EventWriter<EnemyDied>,
+    mut ev_state_update: EventWriter<StateUpdateEvent>,
+    mut enemies: Query<(&Enemy, &EnemyType, &mut Health)>,
+) {
+    let mut dead = HashSet::new();
+    for event in ev_damage.iter() {
+        if dead.contains(&event.entity) {
+            continue;
+        }
+        let Ok((enemy, kind, mut health)) = enemies.get_mut(event.entity)
+        else {
+            continue;
+        };
+        health.value -= event.amount;
+        if health.value <= 0.0 {
+            info!("Enemy {:?} died", event.entity);
+            dead.insert(event.entity);
+            commands.entity(event.entity).despawn_recursive();
+            ev_died.send(EnemyDied {
+                entity: event.entity,
+                kind: kind.clone(),
+                killer_tower: event.tower,
+                overkill: -health.value,
+            });
+            ev_state_update.send(StateUpdateEvent::EnemyKilled(enemy.reward));
+        }
+    }
+}
@@ -21,10 +21,27 @@ fn locate_lights(
     }
 }
 
+const ROUTE_COLORS: [Color; 6] = [
+    Color::RED,
+    Color::GREEN,
+    Color::BLUE,
+    Color::YELLOW,
+    Color::CYAN,
+    Color::FUCHSIA,
+];
+
 fn display_path(mut lines: ResMut<DebugLines>, paths: Query<&PathManager>) {
     for path in paths.iter() {
-        for (a, b) in path.waypoints.iter().zip(path.waypoints.iter().skip(1)) {
-            lines.line(a.location, b.location, 0.0);
+        for (i, route) in path.routes.values().enumerate() {
+            let color = ROUTE_COLORS[i % ROUTE_COLORS.len()];
+            let samples = route.samples();
+            for ((a_distance, a), (b_distance, b)) in
+                samples.iter().zip(samples.iter().skip(1))
+            {
+                if a_distance != b_distance {
+                    lines.line_colored(*a, *b, 0.0, color);
+                }
+            }
         }
     }
 }
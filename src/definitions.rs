@@ -0,0 +1,59 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+
+/// Game content authored by designers as RON files in the assets folder.
+pub trait RonAsset:
+    TypeUuid + DeserializeOwned + Send + Sync + 'static
+{
+    /// Extensions handled by the loader, e.g. `towers.ron` for
+    /// `base.towers.ron`.
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Turns the asset paths referenced in the file into handles and returns
+    /// them so they get loaded together with the definition.
+    fn resolve(
+        &mut self,
+        _load_context: &LoadContext,
+    ) -> Vec<AssetPath<'static>> {
+        vec![]
+    }
+}
+
+pub struct RonAssetLoader<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for RonAssetLoader<T> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut asset = ron::de::from_bytes::<T>(bytes)?;
+            let dependencies = asset.resolve(load_context);
+            load_context.set_default_asset(
+                LoadedAsset::new(asset).with_dependencies(dependencies),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
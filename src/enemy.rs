@@ -1,14 +1,21 @@
-use bevy::{gltf::Gltf, prelude::*};
-use rand::distributions::WeightedIndex;
-use rand::prelude::*;
-use std::time::Duration;
-use strum::{EnumIter, IntoEnumIterator};
+use bevy::{
+    asset::{AssetPath, LoadContext},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
 
 use crate::{
-    pathmanager::PathManager, GameAssets, GameState, HitEvent, PhysicsBundle,
-    StateUpdateEvent,
+    pathmanager::PathManager, Armor, DamageEvent, GameState, HitEvent,
+    PhysicsBundle, Resistances, RonAsset, RonAssetLoader, StateUpdateEvent,
+    StatusEffects,
 };
 
+/// Falling enemies resting on ground colliders slightly above the landing
+/// waypoint have landed as well.
+const LANDING_TOLERANCE: f32 = 0.5;
+
 #[derive(Reflect, Component)]
 pub struct Waypoint {
     pub coords: Transform,
@@ -17,6 +24,20 @@ pub struct Waypoint {
 #[derive(Reflect, Component)]
 pub struct Enemy {
     pub speed: f32,
+    /// Money the player earns for killing this enemy.
+    pub reward: f32,
+    /// Damage dealt to the [`crate::PlayerBase`] when reaching a portal.
+    pub leak_damage: f32,
+}
+
+/// Id of the [`EnemyArchetype`] an enemy was spawned from.
+#[derive(Debug, Reflect, Component, Clone, PartialEq, Eq, Hash)]
+pub struct EnemyType(pub String);
+
+impl std::fmt::Display for EnemyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 #[derive(Reflect, Component)]
@@ -24,269 +45,348 @@ pub struct Health {
     pub value: f32,
 }
 
+/// All enemies that can appear in waves, loaded from a `*.enemies.ron` file.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "1428a1e5-538f-4ed3-a7d1-48c7a2e5d3bc"]
+pub struct EnemyCatalog {
+    pub enemies: Vec<EnemyArchetype>,
+}
+
+impl EnemyCatalog {
+    pub fn get(&self, kind: &EnemyType) -> Option<&EnemyArchetype> {
+        self.enemies.iter().find(|enemy| enemy.id == kind.0)
+    }
+
+    /// The scenes and animations of all enemies.
+    pub fn dependencies(&self) -> Vec<HandleUntyped> {
+        self.enemies
+            .iter()
+            .flat_map(|enemy| {
+                let animation = enemy.animation_handle.as_ref();
+                std::iter::once(enemy.scene_handle.clone_untyped())
+                    .chain(animation.map(Handle::clone_untyped))
+            })
+            .collect()
+    }
+}
+
+impl RonAsset for EnemyCatalog {
+    const EXTENSIONS: &'static [&'static str] = &["enemies.ron"];
+
+    fn resolve(
+        &mut self,
+        load_context: &LoadContext,
+    ) -> Vec<AssetPath<'static>> {
+        let mut dependencies = vec![];
+        for enemy in &mut self.enemies {
+            enemy.scene_handle = load_context.get_handle(enemy.scene.as_str());
+            dependencies.push(AssetPath::from(enemy.scene.as_str()).to_owned());
+            if let Some(animation) = &enemy.animation {
+                enemy.animation_handle =
+                    Some(load_context.get_handle(animation.as_str()));
+                dependencies
+                    .push(AssetPath::from(animation.as_str()).to_owned());
+            }
+        }
+        dependencies
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct EnemyArchetype {
+    pub id: String,
+    pub speed: f32,
+    pub health: f32,
+    pub reward: f32,
+    pub scale: f32,
+    pub scene: String,
+    #[serde(skip)]
+    pub scene_handle: Handle<Scene>,
+    /// Animation clip played in a loop while the enemy is alive.
+    #[serde(default)]
+    pub animation: Option<String>,
+    #[serde(skip)]
+    pub animation_handle: Option<Handle<AnimationClip>>,
+    #[serde(default)]
+    pub armor: Armor,
+    #[serde(default)]
+    pub resistances: Resistances,
+    /// Damage dealt to the [`crate::PlayerBase`] when reaching a portal.
+    #[serde(default = "default_leak_damage")]
+    pub leak_damage: f32,
+}
+
+fn default_leak_damage() -> f32 {
+    1.0
+}
+
+impl EnemyArchetype {
+    pub fn kind(&self) -> EnemyType {
+        EnemyType(self.id.clone())
+    }
+}
+
 #[derive(Reflect, Component)]
 pub struct PathProgress {
     path: Entity,
+    route: i32,
     progress: f32,
 }
 
 impl PathProgress {
-    pub fn new(path: Entity) -> Self {
-        Self { path, progress: 0. }
+    pub fn new(path: Entity, route: i32) -> Self {
+        Self {
+            path,
+            route,
+            progress: 0.,
+        }
+    }
+
+    pub fn route(&self) -> i32 {
+        self.route
+    }
+
+    /// Distance walked along the route.
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Distance left to the end of the route, `None` if the route is gone.
+    pub fn remaining(&self, paths: &Query<&PathManager>) -> Option<f32> {
+        paths
+            .get(self.path)
+            .ok()
+            .and_then(|path| path.route(self.route))
+            .map(|route| route.length() - self.progress)
     }
 }
 
+/// An enemy that left its route at a falling waypoint and drops under gravity
+/// until it reaches the height of the waypoint it lands on.
+#[derive(Reflect, Component)]
+pub struct Falling {
+    pub landing: Vec3,
+}
+
 pub fn enemy_plugin(app: &mut App) {
     app.register_type::<Waypoint>()
         .register_type::<Enemy>()
+        .register_type::<EnemyType>()
         .register_type::<PathProgress>()
-        .insert_resource(WaveState::default())
-        .add_system(enemy_spawner)
-        .add_system(move_enemies.after(enemy_spawner))
+        .register_type::<Falling>()
+        .register_type::<Armor>()
+        .register_type::<Resistances>()
+        .add_asset::<EnemyCatalog>()
+        .init_asset_loader::<RonAssetLoader<EnemyCatalog>>()
+        .add_system(move_enemies.run_if(in_state(GameState::WaveRunning)))
+        .add_system(
+            land_falling_enemies.run_if(in_state(GameState::WaveRunning)),
+        )
         .add_system(hit_event_handler)
-        .add_system(state_update_handler)
-        .add_system(enemy_reaches_portal_handler)
-        .add_system(wave_timer_system);
-}
-
-fn wave_timer_system(
-    mut state: ResMut<WaveState>,
-    mut ev_game_state: EventWriter<StateUpdateEvent>,
-    time: Res<Time>,
-) {
-    state.timer.tick(time.delta());
-    if state.timer.just_finished() {
-        ev_game_state.send(StateUpdateEvent::EndWave);
-    }
+        .add_system(enemy_reaches_portal_handler);
 }
 
+/// Turns hits into [`DamageEvent`]s and applies their status effects.
 fn hit_event_handler(
     mut ev_hit: EventReader<HitEvent>,
-    mut enemies: Query<(Entity, &mut Health), With<Enemy>>,
-    mut commands: Commands,
-    mut ev_status_update: EventWriter<StateUpdateEvent>,
+    mut enemies: Query<
+        (
+            Option<&Armor>,
+            Option<&Resistances>,
+            Option<&mut StatusEffects>,
+        ),
+        With<Enemy>,
+    >,
+    mut ev_damage: EventWriter<DamageEvent>,
 ) {
     for event in ev_hit.iter() {
-        for (ent, mut health) in &mut enemies {
-            if ent == event.entity {
-                let mut force = event.force;
-
-                for side_effect in &event.side_effects {
-                    match side_effect {
-                        crate::TowerSideEffects::WeakShot(value) => {
-                            force -= value
-                        }
-                        crate::TowerSideEffects::HealShot(value) => {
-                            health.value += value
-                        }
-                    }
-                }
-
-                if health.value <= 0.0 {
-                    info!("Enemy {:?} died", ent);
-                    commands.entity(ent).despawn_recursive();
-                    ev_status_update.send(StateUpdateEvent::EnemyKilled(50.0));
-                }
-
-                health.value -= 0.1 * force;
+        let Ok((armor, resistances, status_effects)) =
+            enemies.get_mut(event.entity)
+        else {
+            continue;
+        };
+        let mut force = event.force;
+        let mut heal = 0.0;
+        for side_effect in &event.side_effects {
+            match side_effect {
+                crate::TowerSideEffects::WeakShot(value) => force -= value,
+                crate::TowerSideEffects::HealShot(value) => heal += value,
+                // Applied by the tower
+                _ => {}
             }
         }
-    }
-}
-
-#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, EnumIter)]
-enum EnemyTypes {
-    Drone,
-    Barge,
-}
-
-#[derive(Resource)]
-struct WaveState {
-    game_state: GameState,
-    timer: Timer,
-    spawn_timer: Timer,
-    wave_count: i32,
-    enemy_weights: WeightedIndex<i32>,
-}
 
-impl Default for WaveState {
-    fn default() -> Self {
-        Self {
-            game_state: GameState::TowerUpgrade,
-            timer: Timer::from_seconds(40.0, TimerMode::Once),
-            spawn_timer: Timer::from_seconds(1.5, TimerMode::Repeating),
-            wave_count: 1,
-            enemy_weights: WeightedIndex::new([100, 15]).unwrap(),
+        let damage = event.damage_type.damage(
+            force,
+            armor.copied().unwrap_or_default(),
+            resistances.unwrap_or(&Resistances::default()),
+        );
+        ev_damage.send(DamageEvent {
+            entity: event.entity,
+            amount: damage - heal,
+            tower: event.tower,
+        });
+        if let Some(mut status_effects) = status_effects {
+            for status in &event.status_effects {
+                status_effects.apply(*status);
+            }
         }
     }
 }
 
-fn state_update_handler(
-    mut wave_state: ResMut<WaveState>,
-    mut ev_status_reader: EventReader<StateUpdateEvent>,
-) {
-    for event in ev_status_reader.iter() {
-        match event {
-            StateUpdateEvent::StartWave {
-                spawn_interval,
-                time_of_wave,
-            } => {
-                wave_state.game_state = GameState::RunningWave;
-                wave_state
-                    .timer
-                    .set_duration(Duration::from_secs_f32(*time_of_wave));
-                wave_state.timer.reset();
-                wave_state
-                    .spawn_timer
-                    .set_duration(Duration::from_secs_f32(*spawn_interval));
-                wave_state.spawn_timer.reset();
-                wave_state.spawn_timer.unpause();
-            }
-            StateUpdateEvent::GameWon => {
-                wave_state.game_state = GameState::GameWon;
-                wave_state.spawn_timer.pause();
-            }
-            StateUpdateEvent::GameLost => {
-                wave_state.game_state = GameState::GameLost;
-                wave_state.spawn_timer.pause();
-            }
-            StateUpdateEvent::EndWave => {
-                wave_state.game_state = GameState::TowerUpgrade;
-                wave_state.wave_count += 1;
-                wave_state.spawn_timer.pause();
-            }
-            _ => {}
-        }
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    archetype: &EnemyArchetype,
+    path: Entity,
+    route: i32,
+    position: Vec3,
+) -> Entity {
+    let mut player = AnimationPlayer::default();
+    if let Some(animation) = &archetype.animation_handle {
+        player.play(animation.clone()).repeat();
     }
+
+    commands
+        .spawn((
+            SpatialBundle {
+                transform: Transform::from_translation(position)
+                    .with_scale(Vec3::splat(archetype.scale)),
+                ..Default::default()
+            },
+            Name::new(format!("Enemy {}", archetype.id)),
+            Enemy {
+                speed: archetype.speed,
+                reward: archetype.reward,
+                leak_damage: archetype.leak_damage,
+            },
+            archetype.kind(),
+            archetype.armor,
+            archetype.resistances.clone(),
+            Health {
+                value: archetype.health,
+            },
+            PathProgress::new(path, route),
+            StatusEffects::default(),
+            PhysicsBundle::moving_entity().make_kinematic(),
+        ))
+        .with_children(|commands| {
+            commands.spawn((
+                SceneBundle {
+                    scene: archetype.scene_handle.clone(),
+                    ..Default::default()
+                },
+                player,
+            ));
+        })
+        .id()
 }
 
-fn enemy_spawner(
+fn move_enemies(
     mut commands: Commands,
-    mut wave_state: ResMut<WaveState>,
-    paths: Query<(Entity, &PathManager)>,
+    mut enemies: Query<
+        (
+            Entity,
+            &Enemy,
+            &mut Transform,
+            &mut PathProgress,
+            Option<&StatusEffects>,
+        ),
+        Without<Falling>,
+    >,
+    paths: Query<&PathManager>,
+    rapier_config: Res<RapierConfiguration>,
     time: Res<Time>,
-    assets: Res<GameAssets>,
-    assets_gltf: Res<Assets<Gltf>>,
 ) {
-    if !matches!(wave_state.game_state, GameState::TowerUpgrade) {
-        wave_state.spawn_timer.tick(time.delta());
-        if wave_state.spawn_timer.just_finished() {
-            match paths.get_single() {
-                Ok((path, path_manager)) => {
-                    if let Some(path_start) = path_manager.get_start() {
-                        let mut rng = thread_rng();
-
-                        let spawn_type: EnemyTypes = EnemyTypes::iter()
-                            .collect::<Vec<EnemyTypes>>()
-                            [wave_state.enemy_weights.sample(&mut rng)]
-                        .clone();
-
-                        let (speed, health, scene_handle, player) =
-                            match spawn_type {
-                                EnemyTypes::Drone => {
-                                    let drone = assets_gltf
-                                        .get(&assets.enemy_observer_drone)
-                                        .unwrap();
-                                    let mut player = AnimationPlayer::default();
-
-                                    if let Some(animation) =
-                                        drone.animations.first()
-                                    {
-                                        player
-                                            .play(animation.clone_weak())
-                                            .repeat();
-                                    }
-                                    (
-                                        1.5,
-                                        2.0,
-                                        drone.default_scene.clone().unwrap(),
-                                        player,
-                                    )
-                                }
-                                EnemyTypes::Barge => {
-                                    let barge =
-                                        assets_gltf.get(&assets.barge).unwrap();
-                                    let mut player = AnimationPlayer::default();
-
-                                    if let Some(animation) =
-                                        barge.animations.first()
-                                    {
-                                        player
-                                            .play(animation.clone_weak())
-                                            .repeat();
-                                    }
-
-                                    (
-                                        1.0,
-                                        4.0,
-                                        barge.default_scene.clone().unwrap(),
-                                        player,
-                                    )
-                                }
-                            };
+    for (entity, enemy, mut transform, mut progress, status_effects) in
+        &mut enemies
+    {
+        let Some(route) = paths
+            .get(progress.path)
+            .ok()
+            .and_then(|path| path.route(progress.route))
+        else {
+            continue;
+        };
+        let speed = enemy.speed
+            * status_effects.map_or(1.0, StatusEffects::speed_multiplier);
+        let next = progress.progress + speed * time.delta_seconds();
+        // Routes without waypoints have nowhere to walk or fall to.
+        let Some(position) = route.get_position(next) else {
+            continue;
+        };
 
-                        commands
-                            .spawn((
-                                SpatialBundle {
-                                    transform: Transform::from_translation(
-                                        path_start.location,
-                                    )
-                                    .with_scale(Vec3::new(3.5, 3.5, 3.5)),
-                                    ..Default::default()
-                                },
-                                Name::new(format!("Enemy {:?}", spawn_type)),
-                                Enemy { speed },
-                                Health { value: health },
-                                PathProgress::new(path),
-                                PhysicsBundle::moving_entity().make_kinematic(),
-                            ))
-                            .with_children(|commands| {
-                                commands.spawn((
-                                    SceneBundle {
-                                        scene: scene_handle,
-                                        ..Default::default()
-                                    },
-                                    player,
-                                ));
-                            });
-                    }
-                }
-                Err(_) => {}
+        if let Some((distance, from, landing)) =
+            route.fall_between(progress.progress, next)
+        {
+            progress.progress = distance;
+            transform.translation = from.location;
+            commands.entity(entity).insert((
+                RigidBody::Dynamic,
+                Velocity::linear(fall_velocity(
+                    from.location,
+                    landing.location,
+                    rapier_config.gravity,
+                )),
+                Falling {
+                    landing: landing.location,
+                },
+            ));
+        } else {
+            progress.progress = next;
+            transform.translation = position;
+            let tangent = route.get_tangent(progress.progress);
+            let heading = Vec3::new(tangent.x, 0.0, tangent.z);
+            if heading != Vec3::ZERO {
+                transform.look_to(heading, Vec3::Y);
             }
         }
     }
 }
 
-fn move_enemies(
-    mut enemies: Query<(&Enemy, &mut Transform, &mut PathProgress)>,
-    paths: Query<&PathManager>,
-    time: Res<Time>,
+/// Horizontal velocity that makes a body dropped at `from` land on `to`.
+fn fall_velocity(from: Vec3, to: Vec3, gravity: Vec3) -> Vec3 {
+    if gravity.y >= 0.0 {
+        return Vec3::ZERO;
+    }
+    let duration = (2.0 * (from.y - to.y) / -gravity.y).sqrt();
+    Vec3::new(to.x - from.x, 0.0, to.z - from.z) / duration
+}
+
+fn land_falling_enemies(
+    mut commands: Commands,
+    mut enemies: Query<(Entity, &mut Transform, &Falling)>,
 ) {
-    for (enemy, mut transform, mut progress) in &mut enemies {
-        progress.progress += enemy.speed * time.delta_seconds();
-        transform.translation = paths
-            .get(progress.path)
-            .unwrap()
-            .get_position(progress.progress);
+    for (entity, mut transform, falling) in &mut enemies {
+        if transform.translation.y <= falling.landing.y + LANDING_TOLERANCE {
+            transform.translation = falling.landing;
+            commands
+                .entity(entity)
+                .insert((RigidBody::KinematicPositionBased, Velocity::zero()))
+                .remove::<Falling>();
+        }
     }
 }
 
 fn enemy_reaches_portal_handler(
     mut commands: Commands,
-    enemies: Query<(Entity, &GlobalTransform), With<Enemy>>,
+    enemies: Query<(Entity, &Enemy, &GlobalTransform, &PathProgress)>,
     path_manager: Query<&PathManager>,
     mut ev_state_update: EventWriter<StateUpdateEvent>,
 ) {
-    if let Ok(manager) = path_manager.get_single() {
-        for (enemy_entity, enemy_pos) in &enemies {
-            if let Some(end) = &manager.get_end() {
-                if enemy_pos.translation().distance(end.location)
-                    <= manager.despawn_distance
-                {
-                    debug!("Entity {:?} reached end of path", enemy_entity);
-                    commands.entity(enemy_entity).despawn_recursive();
-                    ev_state_update.send(StateUpdateEvent::EnemyReachedPortal);
-                }
+    for (enemy_entity, enemy, enemy_pos, progress) in &enemies {
+        let Ok(manager) = path_manager.get(progress.path) else {
+            continue;
+        };
+        let end = manager.route(progress.route).and_then(|r| r.get_end());
+        if let Some(end) = &end {
+            if enemy_pos.translation().distance(end.location)
+                <= manager.despawn_distance
+            {
+                debug!("Entity {:?} reached end of path", enemy_entity);
+                commands.entity(enemy_entity).despawn_recursive();
+                ev_state_update.send(StateUpdateEvent::EnemyReachedPortal(
+                    enemy.leak_damage,
+                ));
             }
         }
     }
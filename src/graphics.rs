@@ -216,12 +216,17 @@ fn particle_system_events(
         transform: new_transform,
     } in events.iter()
     {
-        let Ok((_effect, mut spawner, mut transform)) = effect.get_mut(match system {
-            ParticleSystemType::Landing => systems.landing,
-            ParticleSystemType::MuzzleFlash => systems.muzzle_flash,
-            ParticleSystemType::Impact => systems.impact,
-            ParticleSystemType::Portal => systems.portal,
-        }) else {println!("ERROR 401"); return;};
+        let Ok((_effect, mut spawner, mut transform)) =
+            effect.get_mut(match system {
+                ParticleSystemType::Landing => systems.landing,
+                ParticleSystemType::MuzzleFlash => systems.muzzle_flash,
+                ParticleSystemType::Impact => systems.impact,
+                ParticleSystemType::Portal => systems.portal,
+            })
+        else {
+            println!("ERROR 401");
+            return;
+        };
         *transform = new_transform.clone();
         spawner.reset();
         if !spawner.is_active() {
@@ -239,7 +244,7 @@ fn health_loss_effects(
     if events
         .iter()
         .filter(|w| match w {
-            StateUpdateEvent::EnemyReachedPortal => true,
+            StateUpdateEvent::EnemyReachedPortal(_) => true,
             _ => false,
         })
         .count()
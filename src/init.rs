@@ -1,9 +1,10 @@
-use bevy::{gltf::Gltf, prelude::*};
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::{EnemyCatalog, GameState, LevelRegistry, TowerCatalog};
 
 #[derive(Resource)]
 pub struct GameAssets {
     font: Handle<Font>,
-    map: Handle<Gltf>,
     tower_base_bright: Handle<Scene>,
     tower_base_purple: Handle<Scene>,
     tower_base_bad: Handle<Scene>,
@@ -14,14 +15,10 @@ pub struct GameAssets {
     pub gun_a: Handle<Scene>,
     pub default_collider_color: Handle<StandardMaterial>,
     pub tower_base_selected_color: Handle<StandardMaterial>,
-    pub enemy_observer_drone: Handle<Gltf>,
-    pub enemy_drone_animation: Handle<AnimationClip>,
     pub ball_projectile_color: Handle<StandardMaterial>,
-    pub barge: Handle<Gltf>,
-    pub barge_swaying: Handle<AnimationClip>,
-    pub bullet_scene: Handle<Scene>,
-    pub sniper_bullet_scene: Handle<Scene>,
-    pub rocket_scene: Handle<Scene>,
+    pub towers: Handle<TowerCatalog>,
+    pub enemies: Handle<EnemyCatalog>,
+    pub levels: Handle<LevelRegistry>,
 }
 
 pub enum Scenes {
@@ -43,16 +40,120 @@ impl GameAssets {
         self.font.clone()
     }
 
-    pub fn map(&self) -> &Handle<Gltf> {
-        &self.map
-    }
     pub fn get_capsule_shape(&self) -> &Handle<Mesh> {
         &self.capsule_shape
     }
+
+    /// All handles that are loaded from the assets folder.
+    pub fn file_handles(&self) -> Vec<HandleUntyped> {
+        vec![
+            self.font.clone_untyped(),
+            self.tower_base_bright.clone_untyped(),
+            self.tower_base_purple.clone_untyped(),
+            self.tower_base_bad.clone_untyped(),
+            self.tower_slice_a.clone_untyped(),
+            self.ring_a.clone_untyped(),
+            self.gun_a.clone_untyped(),
+            self.towers.clone_untyped(),
+            self.enemies.clone_untyped(),
+            self.levels.clone_untyped(),
+        ]
+    }
+}
+
+/// How far loading the [`GameAssets`] and the files referenced by the
+/// catalogs got, updated while in [`GameState::Loading`].
+#[derive(Resource, Default, Debug)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub total: usize,
+    /// Paths of the assets that failed to load.
+    pub failed: Vec<String>,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.loaded + self.failed.len()) as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.total > 0 && self.loaded + self.failed.len() == self.total
+    }
 }
 
 pub fn initialization_plugin(app: &mut App) {
-    app.add_startup_system(asset_loading.in_base_set(StartupSet::PreStartup));
+    app.add_state::<GameState>()
+        .init_resource::<LoadingProgress>()
+        .add_startup_system(asset_loading.in_base_set(StartupSet::PreStartup))
+        .add_system(check_loading.in_set(OnUpdate(GameState::Loading)));
+}
+
+/// Moves on to the main menu once every asset and the files referenced by
+/// the catalogs finished loading, failed ones are reported and left out.
+fn check_loading(
+    assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+    enemy_catalogs: Res<Assets<EnemyCatalog>>,
+    mut progress: ResMut<LoadingProgress>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut handles = assets.file_handles();
+    let dependencies = tower_catalogs
+        .get(&assets.towers)
+        .map(TowerCatalog::dependencies)
+        .into_iter()
+        .chain(
+            enemy_catalogs
+                .get(&assets.enemies)
+                .map(EnemyCatalog::dependencies),
+        )
+        .flatten();
+    for handle in dependencies {
+        if !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+    let mut loaded = 0;
+    let mut failed = vec![];
+    for handle in &handles {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => failed.push(asset_path(&asset_server, handle)),
+            _ => {}
+        }
+    }
+    *progress = LoadingProgress {
+        loaded,
+        total: handles.len(),
+        failed,
+    };
+
+    if progress.is_done() {
+        for path in &progress.failed {
+            error!("Failed to load {}", path);
+        }
+        info!("Loaded {} of {} assets", progress.loaded, progress.total);
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+/// The path `handle` was loaded from, for reporting failed assets.
+pub(crate) fn asset_path(
+    asset_server: &AssetServer,
+    handle: &HandleUntyped,
+) -> String {
+    match asset_server.get_handle_path(handle) {
+        Some(path) => match path.label() {
+            Some(label) => format!("{}#{}", path.path().display(), label),
+            None => path.path().display().to_string(),
+        },
+        None => format!("{:?}", handle.id()),
+    }
 }
 
 fn asset_loading(
@@ -68,28 +169,22 @@ fn asset_loading(
 
     commands.insert_resource(GameAssets {
         font: assets.load("QuattrocentoSans-Bold.ttf"),
-        map: assets.load("map_a_0.2.glb"),
         tower_base_bright: assets.load("tower_base_a_bright.glb#Scene0"),
         tower_base_purple: assets.load("tower_base_a_purple.glb#Scene0"),
         tower_base_bad: assets.load("tower_base_bad.glb#Scene0"),
         capsule_shape,
         tower_slice_a: assets.load("tower_slice_a.glb#Scene0"),
         gun_a: assets.load("gun_a.glb#Scene0"),
-        ring_a: assets.load("ring_a#Scene0"),
-        enemy_observer_drone: assets.load("enemy_observer_drone.glb"),
-        enemy_drone_animation: assets
-            .load("enemy_observer_drone.glb#Animation0"),
+        ring_a: assets.load("ring_a.glb#Scene0"),
         tower_base_selected_color,
         default_collider_color,
         ball_projectile_color: materials.add(StandardMaterial {
             emissive: Color::rgb_linear(2.0, 13.99, 5.32),
             ..Default::default()
         }),
-        barge: assets.load("enemy_cargo_hover_raft.glb"),
-        barge_swaying: assets.load("enemy_cargo_hover_raft.glb#Animation0"),
-        bullet_scene: assets.load("projectile_gun_bullet.glb#Scene0"),
-        sniper_bullet_scene: assets.load("projectile_sniper_bullet.glb#Scene0"),
-        rocket_scene: assets.load("projectile_rocket.glb#Scene0"),
+        towers: assets.load("data/base.towers.ron"),
+        enemies: assets.load("data/base.enemies.ron"),
+        levels: assets.load("data/base.levels.ron"),
         shpere_shape: meshes.add(
             shape::Icosphere {
                 radius: 0.5,
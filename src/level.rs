@@ -0,0 +1,200 @@
+use bevy::{asset::LoadState, gltf::Gltf, prelude::*, reflect::TypeUuid};
+use serde::Deserialize;
+
+use crate::{
+    init::asset_path, pathmanager::PathManager, Enemy, GameAssets, GameState,
+    MapLoadReport, RonAsset, RonAssetLoader, Tower, TowerBase, WaveSchedule,
+};
+
+/// All levels that can be picked in the level select, loaded from a
+/// `*.levels.ron` file.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "c2a6f0d4-3b1e-4f7a-8e5d-6a9b0c1d2e3f"]
+pub struct LevelRegistry {
+    pub levels: Vec<LevelDefinition>,
+}
+
+impl LevelRegistry {
+    pub fn get(&self, name: &str) -> Option<&LevelDefinition> {
+        self.levels.iter().find(|level| level.name == name)
+    }
+}
+
+impl RonAsset for LevelRegistry {
+    const EXTENSIONS: &'static [&'static str] = &["levels.ron"];
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LevelDefinition {
+    pub name: String,
+    /// GLTF file with the map, its tower bases and proxies.
+    pub map: String,
+    pub waves: String,
+    /// GLTF file whose meshes are only used as terrain colliders.
+    #[serde(default)]
+    pub collision: Option<String>,
+    pub money: f32,
+    pub health: f32,
+}
+
+/// The level that is played, its map and waves are only loaded once it is
+/// picked.
+#[derive(Resource)]
+pub struct CurrentLevel {
+    pub definition: LevelDefinition,
+    pub map: Handle<Gltf>,
+    pub waves: Handle<WaveSchedule>,
+    pub collision: Option<Handle<Gltf>>,
+}
+
+/// Whether a level waits for its GLTF files before it is entered. The
+/// [`crate::headless_app`] has no GLTF loader and turns this off.
+#[derive(Resource)]
+pub struct LoadLevelScenes(pub bool);
+
+impl Default for LoadLevelScenes {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Files of the last started level that failed to load, the level was left
+/// for the main menu.
+#[derive(Resource, Debug)]
+pub struct LevelLoadFailure {
+    pub level: String,
+    pub failed: Vec<String>,
+}
+
+pub enum LevelEvent {
+    /// Starts the level with this name from the main menu.
+    Start(String),
+    /// Returns to the main menu.
+    Leave,
+}
+
+pub fn level_plugin(app: &mut App) {
+    app.add_asset::<LevelRegistry>()
+        .init_asset_loader::<RonAssetLoader<LevelRegistry>>()
+        .init_resource::<LoadLevelScenes>()
+        .add_event::<LevelEvent>()
+        .add_system(level_event_handler)
+        .add_system(
+            check_level_loading.in_set(OnUpdate(GameState::LoadingLevel)),
+        )
+        .add_system(despawn_level.in_schedule(OnEnter(GameState::MainMenu)));
+}
+
+fn level_event_handler(
+    mut commands: Commands,
+    mut ev_level: EventReader<LevelEvent>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    assets: Res<GameAssets>,
+    registries: Res<Assets<LevelRegistry>>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in ev_level.iter() {
+        match event {
+            LevelEvent::Start(name) => {
+                if state.0 != GameState::MainMenu {
+                    warn!("Leave the level before starting {}", name);
+                    continue;
+                }
+                let level = registries
+                    .get(&assets.levels)
+                    .and_then(|registry| registry.get(name));
+                let Some(level) = level else {
+                    warn!("No level {} found", name);
+                    continue;
+                };
+                info!("Starting level {}", name);
+                commands.remove_resource::<LevelLoadFailure>();
+                commands.insert_resource(CurrentLevel {
+                    definition: level.clone(),
+                    map: asset_server.load(level.map.as_str()),
+                    waves: asset_server.load(level.waves.as_str()),
+                    collision: level
+                        .collision
+                        .as_ref()
+                        .map(|collision| asset_server.load(collision.as_str())),
+                });
+                next_state.set(GameState::LoadingLevel);
+            }
+            LevelEvent::Leave => {
+                if state.0.in_level() {
+                    next_state.set(GameState::MainMenu);
+                }
+            }
+        }
+    }
+}
+
+/// Enters the level once its files are loaded, or goes back to the main menu
+/// if its map or waves failed to load. A collision scene that failed to load
+/// is reported by the map instead.
+fn check_level_loading(
+    mut commands: Commands,
+    level: Res<CurrentLevel>,
+    scenes: Res<LoadLevelScenes>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut handles = vec![level.waves.clone_untyped()];
+    let mut collision_loading = false;
+    if scenes.0 {
+        handles.push(level.map.clone_untyped());
+        collision_loading = level.collision.as_ref().is_some_and(|handle| {
+            !matches!(
+                asset_server.get_load_state(handle),
+                LoadState::Loaded | LoadState::Failed
+            )
+        });
+    }
+    let failed = handles
+        .iter()
+        .filter(|handle| {
+            asset_server.get_load_state(*handle) == LoadState::Failed
+        })
+        .map(|handle| asset_path(&asset_server, handle))
+        .collect::<Vec<_>>();
+
+    if !failed.is_empty() {
+        for path in &failed {
+            error!("Failed to load {}", path);
+        }
+        commands.insert_resource(LevelLoadFailure {
+            level: level.definition.name.clone(),
+            failed,
+        });
+        next_state.set(GameState::MainMenu);
+    } else if !collision_loading
+        && asset_server.get_group_load_state(handles.iter().map(|h| h.id()))
+            == LoadState::Loaded
+    {
+        info!("Loaded level {}", level.definition.name);
+        next_state.set(GameState::Building);
+    }
+}
+
+/// Removes the map with everything spawned on it. Projectiles are children of
+/// their tower, bases restored by selling or moving a tower have no parent.
+/// The issues of the map go with it.
+fn despawn_level(
+    mut commands: Commands,
+    mut report: ResMut<MapLoadReport>,
+    entities: Query<
+        Entity,
+        Or<(
+            With<PathManager>,
+            With<Enemy>,
+            With<Tower>,
+            (With<TowerBase>, Without<Parent>),
+        )>,
+    >,
+) {
+    for entity in &entities {
+        commands.entity(entity).despawn_recursive();
+    }
+    *report = MapLoadReport::default();
+}
@@ -1,46 +1,75 @@
+mod bank;
 mod camera;
+mod damage;
 mod debug;
+mod definitions;
 mod enemy;
 mod graphics;
 mod init;
+mod level;
 mod pathmanager;
 mod physics;
+mod player_base;
 mod projectile;
+mod replay;
+mod rng;
+mod save;
+mod status;
 mod tower;
 mod ui_plugin;
+mod wave;
 mod world;
 
 use bevy::{
+    gltf::{Gltf, GltfMesh, GltfNode},
     prelude::*,
     render::{
         settings::{WgpuFeatures, WgpuSettings},
         RenderPlugin,
     },
+    scene::ScenePlugin,
+    time::{TimeSystem, TimeUpdateStrategy},
+    utils::Instant,
     window::WindowMode,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 use bevy_mod_picking::*;
 use bevy_rapier3d::{
-    prelude::{NoUserData, RapierPhysicsPlugin},
+    prelude::{
+        NoUserData, RapierConfiguration, RapierPhysicsPlugin, TimestepMode,
+    },
     render::RapierDebugRenderPlugin,
 };
 use debug::debug_plugin;
-use graphics::graphics_plugin;
-use pathmanager::path_manager_plugin;
+use graphics::{graphics_plugin, CreateParticleSystem};
 use seldom_fn_plugin::FnPluginExt;
 
+pub use bank::*;
 pub use camera::*;
+pub use damage::*;
+pub use definitions::*;
 pub use enemy::*;
 pub use init::*;
+pub use level::*;
+pub use pathmanager::*;
 pub use physics::*;
+pub use player_base::*;
 pub use projectile::*;
+pub use replay::*;
+pub use rng::*;
+pub use save::*;
+pub use status::*;
 pub use tower::*;
 pub use ui_plugin::*;
+pub use wave::*;
 pub use world::*;
 
 pub const LAUNCHER_TITLE: &str = "Towering Sideffects";
 
+/// Seconds the clock of a [`headless_app`] advances on every update.
+pub const HEADLESS_TIMESTEP: f32 = 1.0 / 60.0;
+
 pub fn app(fullscreen: bool) -> App {
     let mut wgpu_settings = WgpuSettings::default();
     #[cfg(feature = "particles")]
@@ -55,6 +84,10 @@ pub fn app(fullscreen: bool) -> App {
     let mut app = App::new();
     app.add_plugins(
         DefaultPlugins
+            .set(AssetPlugin {
+                watch_for_changes: cfg!(debug_assertions),
+                ..default()
+            })
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: LAUNCHER_TITLE.to_string(),
@@ -69,12 +102,21 @@ pub fn app(fullscreen: bool) -> App {
     )
     .insert_resource(ClearColor(Color::rgb_linear(0.2, 0.2, 0.2)))
     .fn_plugin(initialization_plugin)
+    .fn_plugin(rng_plugin)
+    .fn_plugin(level_plugin)
     .fn_plugin(path_manager_plugin)
     .fn_plugin(camera_plugin)
     .fn_plugin(world_plugin)
     .fn_plugin(tower_plugin)
     .fn_plugin(enemy_plugin)
+    .fn_plugin(status_plugin)
+    .fn_plugin(damage_plugin)
+    .fn_plugin(wave_plugin)
+    .fn_plugin(bank_plugin)
+    .fn_plugin(player_base_plugin)
     .fn_plugin(projectile_plugin)
+    .fn_plugin(save_plugin)
+    .fn_plugin(replay_plugin)
     .fn_plugin(ui_plugin)
     .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
     .add_plugins(DefaultPickingPlugins)
@@ -88,3 +130,64 @@ pub fn app(fullscreen: bool) -> App {
     }
     app
 }
+
+/// Builds the game without window, renderer, UI or picking. Every
+/// `App::update` advances time by exactly [`HEADLESS_TIMESTEP`], so waves can
+/// be simulated deterministically from tests or on a build server.
+///
+/// GLTF files are never loaded, so levels are entered without waiting for
+/// them and after starting a level with a [`LevelEvent`] the map has to be
+/// provided by spawning a [`PathManager`] manually.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(ScenePlugin)
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<Gltf>()
+        .add_asset::<GltfNode>()
+        .add_asset::<GltfMesh>()
+        .add_event::<CreateParticleSystem>()
+        .insert_resource(TimeUpdateStrategy::ManualInstant(Instant::now()))
+        .insert_resource(LoadLevelScenes(false))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: HEADLESS_TIMESTEP,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .add_system(
+            advance_headless_clock
+                .in_base_set(CoreSet::First)
+                .before(TimeSystem),
+        )
+        .fn_plugin(initialization_plugin)
+        .fn_plugin(rng_plugin)
+        .fn_plugin(level_plugin)
+        .fn_plugin(path_manager_plugin)
+        .fn_plugin(world_plugin)
+        .fn_plugin(tower_plugin)
+        .fn_plugin(enemy_plugin)
+        .fn_plugin(status_plugin)
+        .fn_plugin(damage_plugin)
+        .fn_plugin(wave_plugin)
+        .fn_plugin(bank_plugin)
+        .fn_plugin(player_base_plugin)
+        .fn_plugin(projectile_plugin)
+        .fn_plugin(game_state_plugin)
+        .fn_plugin(save_plugin)
+        .fn_plugin(replay_plugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .fn_plugin(physics_plugin);
+    app
+}
+
+fn advance_headless_clock(mut strategy: ResMut<TimeUpdateStrategy>) {
+    if let TimeUpdateStrategy::ManualInstant(instant) = strategy.as_mut() {
+        *instant += std::time::Duration::from_secs_f32(HEADLESS_TIMESTEP);
+    }
+}
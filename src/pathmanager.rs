@@ -1,15 +1,18 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
 
-use crate::Proxy;
+use crate::{MovementType, Proxy, ProxyKind};
 
 pub fn path_manager_plugin(app: &mut App) {
     app.add_event::<PathManagerUpdate>()
         .add_system(handle_pathmanager_update);
 }
 
+/// All routes of a map, one path per `route_id` of its [`Proxy`] nodes.
 #[derive(Component)]
 pub struct PathManager {
-    pub waypoints: Vec<Proxy>,
+    pub routes: BTreeMap<i32, RoutePath>,
     pub despawn_distance: f32,
 }
 
@@ -18,34 +21,183 @@ const DEFAULT_DESPAWN_DISTANCE: f32 = 0.2;
 impl PathManager {
     pub fn new() -> Self {
         Self {
-            waypoints: vec![],
+            routes: BTreeMap::new(),
             despawn_distance: DEFAULT_DESPAWN_DISTANCE,
         }
     }
 
+    pub fn push(&mut self, proxy: Proxy) {
+        self.routes.entry(proxy.route_id).or_default().push(proxy);
+    }
+
+    pub fn route(&self, route_id: i32) -> Option<&RoutePath> {
+        self.routes.get(&route_id)
+    }
+
+    pub fn route_ids(&self) -> Vec<i32> {
+        self.routes.keys().copied().collect()
+    }
+}
+
+/// The waypoints of a single route ordered by their `node_id`. The first
+/// waypoint is the spawn point of the route.
+///
+/// Enemies walk a Catmull-Rom spline through the waypoints, which is sampled
+/// into a table of arc lengths whenever the waypoints change, so positions are
+/// looked up with a binary search instead of walking all segments.
+///
+/// Enemies don't walk the segment after a [`ProxyKind::Portal`] waypoint, they
+/// are teleported to the next waypoint. After a [`MovementType::Falling`]
+/// waypoint they drop to the next waypoint under gravity. Both segments have no
+/// length, so the progress along the route doesn't change while crossing them.
+#[derive(Default, Clone)]
+pub struct RoutePath {
+    pub waypoints: Vec<Proxy>,
+    /// Walked distance from the start of the route to each waypoint.
+    distances: Vec<f32>,
+    /// Points along the spline with the walked distance up to them.
+    samples: Vec<(f32, Vec3)>,
+}
+
+/// Number of straight pieces each spline segment is approximated with.
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+impl RoutePath {
     pub fn push(&mut self, proxy: Proxy) {
         let mut waypoints = self.waypoints.clone();
         waypoints.push(proxy);
         waypoints.sort_by(|a, b| b.node_id.cmp(&a.node_id));
         waypoints.reverse();
         self.waypoints = waypoints;
+        self.update_samples();
+    }
+
+    pub fn remove(&mut self, node_id: i32) {
+        self.waypoints
+            .retain(|waypoint| waypoint.node_id != node_id);
+        self.update_samples();
     }
 
-    pub fn get_position(&self, progress: f32) -> Vec3 {
-        let mut tail: Vec3 = self.waypoints.first().unwrap().clone().location;
-        let mut progress = progress;
-        for waypoint in &self.waypoints {
-            if tail.distance(waypoint.location) > progress {
-                return tail.lerp(
-                    waypoint.location,
-                    progress / tail.distance(waypoint.location),
-                );
+    fn update_samples(&mut self) {
+        self.distances.clear();
+        self.samples.clear();
+        let mut distance = 0.0;
+        for (i, waypoint) in self.waypoints.iter().enumerate() {
+            if i > 0 && self.is_walked(i - 1) {
+                let [p0, p1, p2, p3] = self.control_points(i - 1);
+                let mut tail = p1;
+                for step in 1..=SAMPLES_PER_SEGMENT {
+                    let t = step as f32 / SAMPLES_PER_SEGMENT as f32;
+                    let point = catmull_rom(p0, p1, p2, p3, t);
+                    distance += tail.distance(point);
+                    self.samples.push((distance, point));
+                    tail = point;
+                }
             } else {
-                progress = progress - tail.distance(waypoint.location);
-                tail = waypoint.clone().location;
+                self.samples.push((distance, waypoint.location));
             }
+            self.distances.push(distance);
         }
-        self.waypoints.last().unwrap().location
+    }
+
+    /// Whether enemies walk from waypoint `i` to the next one.
+    fn is_walked(&self, i: usize) -> bool {
+        let waypoint = &self.waypoints[i];
+        waypoint.kind != ProxyKind::Portal
+            && waypoint.movement_type != MovementType::Falling
+    }
+
+    /// Control points of the spline segment from waypoint `i` to the next
+    /// one. At the ends of a walked stretch the missing neighbours are
+    /// mirrored, so the spline leaves and enters them in a straight line.
+    fn control_points(&self, i: usize) -> [Vec3; 4] {
+        let p1 = self.waypoints[i].location;
+        let p2 = self.waypoints[i + 1].location;
+        let p0 = match i.checked_sub(1) {
+            Some(previous) if self.is_walked(previous) => {
+                self.waypoints[previous].location
+            }
+            _ => 2.0 * p1 - p2,
+        };
+        let p3 = match self.waypoints.get(i + 2) {
+            Some(next) if self.is_walked(i + 1) => next.location,
+            _ => 2.0 * p2 - p1,
+        };
+        [p0, p1, p2, p3]
+    }
+
+    /// The sampled spline, portals and falls are pieces without length.
+    pub fn samples(&self) -> &[(f32, Vec3)] {
+        &self.samples
+    }
+
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Index of the last sample reached at `progress`. With several samples
+    /// at the same distance the one after a portal or fall is used.
+    fn sample_index(&self, progress: f32) -> usize {
+        self.samples
+            .partition_point(|(distance, _)| *distance <= progress)
+            .max(1)
+            - 1
+    }
+
+    /// Position at `progress`, `None` for routes without waypoints.
+    pub fn get_position(&self, progress: f32) -> Option<Vec3> {
+        let i = self.sample_index(progress);
+        match (self.samples.get(i), self.samples.get(i + 1)) {
+            (Some((tail_distance, tail)), Some((head_distance, head))) => Some(
+                tail.lerp(
+                    *head,
+                    ((progress - tail_distance)
+                        / (head_distance - tail_distance))
+                        .clamp(0.0, 1.0),
+                ),
+            ),
+            (Some((_, tail)), None) => Some(*tail),
+            _ => None,
+        }
+    }
+
+    /// Direction enemies walk at `progress`, zero for routes without walked
+    /// segments.
+    pub fn get_tangent(&self, progress: f32) -> Vec3 {
+        // Past the end enemies keep the direction of the last piece.
+        let i = self
+            .sample_index(progress)
+            .min(self.samples.len().saturating_sub(2));
+        match (self.samples.get(i), self.samples.get(i + 1)) {
+            (Some((_, tail)), Some((_, head))) => {
+                (*head - *tail).normalize_or_zero()
+            }
+            _ => Vec3::ZERO,
+        }
+    }
+
+    /// The first falling waypoint that is passed when moving from the
+    /// `from` to the `to` progress, with the progress at that waypoint and
+    /// the waypoint the enemy lands on. Falling waypoints without a lower
+    /// waypoint after them behave like portals.
+    pub fn fall_between(
+        &self,
+        from: f32,
+        to: f32,
+    ) -> Option<(f32, &Proxy, &Proxy)> {
+        self.waypoints
+            .iter()
+            .zip(self.waypoints.iter().skip(1))
+            .zip(&self.distances)
+            .find(|((waypoint, landing), distance)| {
+                waypoint.movement_type == MovementType::Falling
+                    && landing.location.y < waypoint.location.y
+                    && from < **distance
+                    && **distance <= to
+            })
+            .map(|((waypoint, landing), distance)| {
+                (*distance, waypoint, landing)
+            })
     }
 
     pub fn get_start(&self) -> Option<Proxy> {
@@ -57,6 +209,16 @@ impl PathManager {
     }
 }
 
+/// Point at `t` between `p1` and `p2` of a uniform Catmull-Rom spline.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
 #[derive(Debug)]
 pub enum PathManagerUpdate {
     AddNode(Proxy),
@@ -76,12 +238,11 @@ fn handle_pathmanager_update(
                 }
                 PathManagerUpdate::RemoveNode(p) => {
                     info!("Removing Proxy Waypoint {} from path", p);
-                    path_manager.waypoints = path_manager
-                        .waypoints
-                        .clone()
-                        .into_iter()
-                        .filter(|ve| ve.node_id != p.node_id)
-                        .collect();
+                    if let Some(route) =
+                        path_manager.routes.get_mut(&p.route_id)
+                    {
+                        route.remove(p.node_id);
+                    }
                 }
             },
             Err(_) => info!("No Pathmanager yet while adding {:#?}", event),
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use crate::GameState;
+
 #[derive(Bundle)]
 pub struct PhysicsBundle {
     flags: ActiveEvents,
@@ -25,13 +27,15 @@ impl PhysicsBundle {
             velocity: Velocity::zero(),
         }
     }
+    /// A collider with the shape of the mesh, `None` if the mesh has no
+    /// triangles to build the shape from.
     pub fn from_mesh(
         mesh: &Mesh,
         collider_shape: &ComputedColliderShape,
-    ) -> Self {
-        let collider = Collider::from_bevy_mesh(mesh, collider_shape).unwrap();
+    ) -> Option<Self> {
+        let collider = Collider::from_bevy_mesh(mesh, collider_shape)?;
         //collider.set_scale(Vec3::new(2.0, 2.0, 2.0), 1);
-        Self {
+        Some(Self {
             flags: ActiveEvents::COLLISION_EVENTS,
             active_collition_types: ActiveCollisionTypes::default()
                 | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
@@ -40,7 +44,7 @@ impl PhysicsBundle {
             rigid_body: RigidBody::Dynamic,
             rotation_contraint: LockedAxes::ROTATION_LOCKED,
             velocity: Velocity::zero(),
-        }
+        })
     }
 
     pub fn make_kinematic(mut self) -> Self {
@@ -48,8 +52,12 @@ impl PhysicsBundle {
         self
     }
 
+    /// Fixed bodies also collide with kinematic ones, so projectiles hit
+    /// terrain.
     pub fn make_fixed(mut self) -> Self {
         self.rigid_body = RigidBody::Fixed;
+        self.active_collition_types = self.active_collition_types
+            | ActiveCollisionTypes::KINEMATIC_STATIC;
         self
     }
 
@@ -64,4 +72,15 @@ impl PhysicsBundle {
     }
 }
 
-pub fn physics_plugin(_app: &mut App) {}
+pub fn physics_plugin(app: &mut App) {
+    app.add_system(pause_physics.in_schedule(OnEnter(GameState::Paused)))
+        .add_system(resume_physics.in_schedule(OnExit(GameState::Paused)));
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+
+use crate::{
+    Bank, CurrentLevel, GameState, StateUpdateEvent, TransactionSource,
+};
+
+pub const HEAL_PRICE: f32 = 100.0;
+pub const HEAL_AMOUNT: f32 = 5.0;
+
+/// The base the enemies walk to, the level is lost once its health is used
+/// up.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct PlayerBase {
+    health: f32,
+}
+
+impl PlayerBase {
+    pub fn new(health: f32) -> Self {
+        Self { health }
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.health -= amount;
+    }
+
+    /// Buys [`HEAL_AMOUNT`] health for [`HEAL_PRICE`], returns whether the
+    /// bank could pay for it.
+    pub fn heal(&mut self, bank: &mut Bank) -> bool {
+        if self.is_destroyed()
+            || !bank.try_spend(TransactionSource::Heal, HEAL_PRICE)
+        {
+            return false;
+        }
+        self.health += HEAL_AMOUNT;
+        true
+    }
+}
+
+pub fn player_base_plugin(app: &mut App) {
+    app.init_resource::<PlayerBase>()
+        .add_system(build_base.in_schedule(OnExit(GameState::MainMenu)))
+        .add_system(damage_and_heal)
+        .add_system(game_over.after(damage_and_heal));
+}
+
+fn build_base(mut base: ResMut<PlayerBase>, level: Res<CurrentLevel>) {
+    *base = PlayerBase::new(level.definition.health);
+}
+
+pub(crate) fn damage_and_heal(
+    mut base: ResMut<PlayerBase>,
+    mut bank: ResMut<Bank>,
+    mut ev_state_update: EventReader<StateUpdateEvent>,
+) {
+    for event in ev_state_update.iter() {
+        match event {
+            StateUpdateEvent::EnemyReachedPortal(damage) => {
+                base.damage(*damage)
+            }
+            StateUpdateEvent::Heal => {
+                base.heal(&mut bank);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ends the level as soon as the base is destroyed.
+fn game_over(
+    base: Res<PlayerBase>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut ev_state_update: EventWriter<StateUpdateEvent>,
+) {
+    let playing = matches!(
+        state.0,
+        GameState::Building | GameState::WaveRunning | GameState::Paused
+    );
+    if base.is_changed() && base.is_destroyed() && playing {
+        info!("The base was destroyed");
+        next_state.set(GameState::Lost);
+        ev_state_update.send(StateUpdateEvent::GameLost);
+    }
+}
@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{tower_shoot, SideEffectBundle, TowerSideEffects};
+use crate::{
+    graphics::{CreateParticleSystem, ParticleSystemType},
+    tower_shoot, DamageType, Enemy, GameState, SideEffectBundle,
+    TimedStatusEffect, TowerSideEffects,
+};
 
 #[derive(Reflect, Component, Default)]
 #[reflect(Component)]
@@ -11,47 +15,140 @@ pub struct Lifetime {
 
 pub struct HitEvent {
     pub entity: Entity,
+    /// The tower that shot the projectile.
+    pub tower: Option<Entity>,
     pub force: f32,
+    pub damage_type: DamageType,
+    pub status_effects: Vec<TimedStatusEffect>,
     pub side_effects: Vec<TowerSideEffects>,
 }
 
+/// A projectile struck `entity`, it hits it and splashes onto the enemies
+/// around.
+pub struct ImpactEvent {
+    pub projectile: Entity,
+    pub entity: Entity,
+}
+
 #[derive(Reflect, Component, Default)]
 pub struct Projectile {
     pub direction: Option<Vec3>,
     pub speed: f32,
     pub force: f32,
+    pub damage_type: DamageType,
     pub target: Option<Entity>,
+    /// The tower that shot the projectile.
+    pub tower: Option<Entity>,
+    /// Enemies this close to the impact are hit as well, with less force the
+    /// further away they are.
+    pub aoe_radius: f32,
+    /// Applied to every enemy that is hit.
+    #[reflect(ignore)]
+    pub status_effects: Vec<TimedStatusEffect>,
+}
+
+impl Projectile {
+    /// Force of the splash on an enemy `distance` away from the impact.
+    pub fn splash_force(&self, distance: f32) -> f32 {
+        if self.aoe_radius <= 0.0 {
+            return 0.0;
+        }
+        self.force * (1.0 - distance / self.aoe_radius).clamp(0.0, 1.0)
+    }
 }
 
 pub fn projectile_plugin(app: &mut App) {
     app.register_type::<Lifetime>()
         .register_type::<Projectile>()
         .add_event::<HitEvent>()
-        .add_system(move_projectile.after(tower_shoot))
-        .add_system(projectile_despawn)
-        .add_system(projectile_collision_detection);
+        .add_event::<ImpactEvent>()
+        .add_system(
+            move_projectile
+                .after(tower_shoot)
+                .run_if(not(in_state(GameState::Paused))),
+        )
+        .add_system(projectile_despawn.run_if(not(in_state(GameState::Paused))))
+        .add_system(projectile_collision_detection)
+        .add_system(projectile_impact.after(projectile_collision_detection));
 }
 
 fn projectile_collision_detection(
+    projectiles: Query<Entity, With<Projectile>>,
+    colliding_entities_query: Query<(Entity, &CollidingEntities)>,
+    mut ev_impact: EventWriter<ImpactEvent>,
+) {
+    for projectile in &projectiles {
+        let hit =
+            colliding_entities_query
+                .iter()
+                .find(|(_, colliding_entities)| {
+                    colliding_entities.contains(projectile)
+                });
+        if let Some((entity, _)) = hit {
+            debug!("Hit!");
+            ev_impact.send(ImpactEvent { projectile, entity });
+        }
+    }
+}
+
+/// Hits the struck entity with the full force of the projectile and the
+/// enemies within its splash radius with less.
+fn projectile_impact(
     mut commands: Commands,
-    projectile_query: Query<(Entity, &Projectile, &SideEffectBundle)>,
-    mut colliding_entities_query: Query<(Entity, &CollidingEntities)>,
+    mut ev_impact: EventReader<ImpactEvent>,
+    projectiles: Query<(&Projectile, &SideEffectBundle, &GlobalTransform)>,
+    enemies: Query<(Entity, &GlobalTransform), With<Enemy>>,
     mut ev_hit_event: EventWriter<HitEvent>,
+    mut ev_particles: EventWriter<CreateParticleSystem>,
 ) {
-    for (entity, colliding_entities) in colliding_entities_query.iter_mut() {
-        for (projectile, projectile_info, side_effects) in
-            projectile_query.iter()
-        {
-            if colliding_entities.contains(projectile) {
-                debug!("Hit!");
-                commands.entity(projectile).despawn_recursive();
+    // Projectiles despawned by earlier impacts, they are still found by the
+    // query.
+    let mut removed = vec![];
+    for ImpactEvent { projectile, entity } in ev_impact.iter() {
+        if removed.contains(projectile) {
+            continue;
+        }
+        let Ok((projectile_info, side_effects, transform)) =
+            projectiles.get(*projectile)
+        else {
+            continue;
+        };
+        commands.entity(*projectile).despawn_recursive();
+        removed.push(*projectile);
+        ev_hit_event.send(HitEvent {
+            entity: *entity,
+            tower: projectile_info.tower,
+            force: projectile_info.force,
+            damage_type: projectile_info.damage_type,
+            status_effects: projectile_info.status_effects.clone(),
+            side_effects: side_effects.side_effects.clone(),
+        });
+
+        if projectile_info.aoe_radius <= 0.0 {
+            continue;
+        }
+        let impact = transform.translation();
+        for (other, enemy) in &enemies {
+            if other == *entity {
+                continue;
+            }
+            let force = projectile_info
+                .splash_force(enemy.translation().distance(impact));
+            if force > 0.0 {
                 ev_hit_event.send(HitEvent {
-                    entity,
-                    force: projectile_info.force,
+                    entity: other,
+                    tower: projectile_info.tower,
+                    force,
+                    damage_type: projectile_info.damage_type,
+                    status_effects: projectile_info.status_effects.clone(),
                     side_effects: side_effects.side_effects.clone(),
                 });
             }
         }
+        ev_particles.send(CreateParticleSystem {
+            system: ParticleSystemType::Impact,
+            transform: Transform::from_translation(impact),
+        });
     }
 }
 
@@ -0,0 +1,513 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    advance_headless_clock, damage_and_heal, in_level,
+    rng::seed_rng,
+    save::{storage, PendingLoad},
+    tower_build, CurrentLevel, GameRng, GameState, LevelEvent,
+    StateUpdateEvent, TargetingMode, Tower, TowerBase, TowerBuildEvent,
+    TowerType, TowerUpgrades, UpgradeSideEffect,
+};
+
+/// The gameplay commands of one level and the length of its frames, enough to
+/// play it again with the same outcome. Levels loaded from a save game are
+/// not recorded, the replay would miss the saved game.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// Name of the [`crate::LevelDefinition`] that was played.
+    pub level: String,
+    /// Seed of the [`GameRng`] at the level start.
+    pub seed: u64,
+    pub commands: Vec<RecordedCommand>,
+    /// Length of every frame in nanoseconds, played back as the time step.
+    pub frame_times: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    /// Frames since the level started.
+    pub frame: u64,
+    pub command: ReplayCommand,
+}
+
+/// Towers are identified by their position, entities differ between runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayCommand {
+    Build {
+        kind: String,
+        position: Vec3,
+    },
+    Upgrade {
+        position: Vec3,
+        effect: TowerUpgrades,
+        side_effect: UpgradeSideEffect,
+    },
+    SetTargeting {
+        position: Vec3,
+        mode: TargetingMode,
+    },
+    Sell {
+        position: Vec3,
+    },
+    Relocate {
+        position: Vec3,
+        /// Position of the tower base the tower is moved onto.
+        to: Vec3,
+    },
+    StartWave,
+    Heal,
+}
+
+impl Replay {
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(text: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn write(&self, slot: &ReplaySlot) -> anyhow::Result<()> {
+        storage::write(&slot.0, &self.to_ron()?)
+    }
+
+    pub fn read(slot: &ReplaySlot) -> anyhow::Result<Self> {
+        Self::from_ron(&storage::read(&slot.0)?)
+    }
+}
+
+/// Where replays are saved to, like [`crate::SaveSlot`].
+#[derive(Resource, Debug, Clone)]
+pub struct ReplaySlot(pub String);
+
+impl Default for ReplaySlot {
+    fn default() -> Self {
+        Self("replay.ron".to_string())
+    }
+}
+
+/// Records the commands of the level that is played.
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    frame: u64,
+    recording: bool,
+}
+
+impl ReplayRecorder {
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Whether the level can be replayed, it wasn't loaded from a save game.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Frames since the level started.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+/// Plays a replay, the level is started from the main menu and the recorded
+/// commands are sent on the frames they were recorded on.
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    next: usize,
+    started: bool,
+    /// The clock of the app steps the time once the replay is over.
+    automatic_time: bool,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next: 0,
+            started: false,
+            automatic_time: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.commands.len()
+    }
+}
+
+pub enum ReplayEvent {
+    /// Writes the replay of the level that is played to the [`ReplaySlot`].
+    Save,
+    /// Plays the replay in the [`ReplaySlot`], only from the main menu.
+    Play,
+}
+
+/// Playback sends the recorded commands and recording picks up the commands
+/// of the player, both before `tower_build` and the state handlers apply
+/// them. Commands take effect on the frame they are recorded on and draw from
+/// the [`GameRng`] in the same order when played back.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplaySet {
+    Playback,
+    /// Before towers are moved or removed, they are recorded by position.
+    Record,
+}
+
+/// Distance between a recorded position and the tower or tower base it
+/// refers to.
+const POSITION_TOLERANCE: f32 = 0.1;
+
+pub fn replay_plugin(app: &mut App) {
+    app.init_resource::<ReplayRecorder>()
+        .init_resource::<ReplaySlot>()
+        .add_event::<ReplayEvent>()
+        .configure_set(ReplaySet::Playback.before(ReplaySet::Record))
+        .configure_set(
+            ReplaySet::Record
+                .before(tower_build)
+                .before(damage_and_heal),
+        )
+        .add_system(
+            seed_replay
+                .after(seed_rng)
+                .in_schedule(OnExit(GameState::MainMenu)),
+        )
+        .add_system(
+            begin_recording
+                .after(seed_replay)
+                .in_schedule(OnExit(GameState::MainMenu)),
+        )
+        .add_system(stop_replay.in_schedule(OnEnter(GameState::MainMenu)))
+        .add_system(
+            count_frames
+                .in_base_set(CoreSet::First)
+                .after(TimeSystem)
+                .run_if(in_level),
+        )
+        .add_system(
+            play_frame_times
+                .in_base_set(CoreSet::First)
+                .after(advance_headless_clock)
+                .before(TimeSystem)
+                .run_if(in_level)
+                .run_if(resource_exists::<ReplayPlayer>()),
+        )
+        .add_system(
+            play_commands
+                .in_set(ReplaySet::Playback)
+                .run_if(in_level)
+                .run_if(resource_exists::<ReplayPlayer>()),
+        )
+        .add_system(record_commands.in_set(ReplaySet::Record).run_if(in_level))
+        .add_system(replay_event_handler)
+        .add_system(
+            start_replay
+                .run_if(in_state(GameState::MainMenu))
+                .run_if(resource_exists::<ReplayPlayer>()),
+        );
+}
+
+fn seed_replay(player: Option<Res<ReplayPlayer>>, mut rng: ResMut<GameRng>) {
+    if let Some(player) = player {
+        *rng = GameRng::from_seed(player.replay.seed);
+    }
+}
+
+fn begin_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    level: Res<CurrentLevel>,
+    rng: Res<GameRng>,
+    pending: Option<Res<PendingLoad>>,
+) {
+    *recorder = ReplayRecorder {
+        replay: Replay {
+            level: level.definition.name.clone(),
+            seed: rng.seed(),
+            commands: vec![],
+            frame_times: vec![],
+        },
+        frame: 0,
+        recording: pending.is_none(),
+    };
+}
+
+fn count_frames(mut recorder: ResMut<ReplayRecorder>, time: Res<Time>) {
+    recorder.frame += 1;
+    let frame_time = time.delta().as_nanos() as u64;
+    recorder.replay.frame_times.push(frame_time);
+}
+
+/// Steps the time by the recorded frame lengths, so the frame rate of the app
+/// playing the replay doesn't matter.
+fn play_frame_times(
+    mut player: ResMut<ReplayPlayer>,
+    recorder: Res<ReplayRecorder>,
+    time: Res<Time>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let frame_time = player.replay.frame_times.get(recorder.frame as usize);
+    match (frame_time, time.last_update()) {
+        (Some(frame_time), Some(last_update)) => {
+            let next_update = last_update + Duration::from_nanos(*frame_time);
+            if matches!(*strategy, TimeUpdateStrategy::Automatic) {
+                player.automatic_time = true;
+            }
+            *strategy = TimeUpdateStrategy::ManualInstant(next_update);
+        }
+        _ => restore_time(&mut player, &mut strategy),
+    }
+}
+
+fn restore_time(player: &mut ReplayPlayer, strategy: &mut TimeUpdateStrategy) {
+    if player.automatic_time {
+        *strategy = TimeUpdateStrategy::Automatic;
+        player.automatic_time = false;
+    }
+}
+
+fn record_commands(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut ev_tower_build: EventReader<TowerBuildEvent>,
+    mut ev_state_update: EventReader<StateUpdateEvent>,
+    towers: Query<&Transform, With<Tower>>,
+    tower_bases: Query<&GlobalTransform, With<TowerBase>>,
+) {
+    let mut commands = vec![];
+    for event in ev_tower_build.iter() {
+        commands.push(match event {
+            TowerBuildEvent::Dispatch { kind, pos, .. } => {
+                ReplayCommand::Build {
+                    kind: kind.0.clone(),
+                    position: *pos,
+                }
+            }
+            TowerBuildEvent::Upgrade {
+                entity,
+                effect,
+                side_effect,
+            } => {
+                let Ok(transform) = towers.get(*entity) else {
+                    continue;
+                };
+                ReplayCommand::Upgrade {
+                    position: transform.translation,
+                    effect: *effect,
+                    side_effect: *side_effect,
+                }
+            }
+            TowerBuildEvent::SetTargeting { entity, mode } => {
+                let Ok(transform) = towers.get(*entity) else {
+                    continue;
+                };
+                ReplayCommand::SetTargeting {
+                    position: transform.translation,
+                    mode: *mode,
+                }
+            }
+            TowerBuildEvent::Sell { entity } => {
+                let Ok(transform) = towers.get(*entity) else {
+                    continue;
+                };
+                ReplayCommand::Sell {
+                    position: transform.translation,
+                }
+            }
+            TowerBuildEvent::Relocate { entity, base } => {
+                let (Ok(transform), Ok(base)) =
+                    (towers.get(*entity), tower_bases.get(*base))
+                else {
+                    continue;
+                };
+                ReplayCommand::Relocate {
+                    position: transform.translation,
+                    to: base.translation(),
+                }
+            }
+        });
+    }
+    for event in ev_state_update.iter() {
+        match event {
+            StateUpdateEvent::StartWave => {
+                commands.push(ReplayCommand::StartWave)
+            }
+            StateUpdateEvent::Heal => commands.push(ReplayCommand::Heal),
+            _ => {}
+        }
+    }
+
+    let frame = recorder.frame;
+    recorder.replay.commands.extend(
+        commands
+            .into_iter()
+            .map(|command| RecordedCommand { frame, command }),
+    );
+}
+
+fn play_commands(
+    mut player: ResMut<ReplayPlayer>,
+    recorder: Res<ReplayRecorder>,
+    mut ev_tower_build: EventWriter<TowerBuildEvent>,
+    mut ev_state_update: EventWriter<StateUpdateEvent>,
+    towers: Query<(Entity, &Transform), With<Tower>>,
+    tower_bases: Query<(Entity, &GlobalTransform), With<TowerBase>>,
+) {
+    let player = player.as_mut();
+    while let Some(recorded) = player.replay.commands.get(player.next) {
+        if recorded.frame > recorder.frame {
+            break;
+        }
+        player.next += 1;
+        match &recorded.command {
+            ReplayCommand::Build { kind, position } => {
+                let Some(base) = tower_base_at(&tower_bases, *position) else {
+                    warn!("Replay diverged, no tower base at {}", position);
+                    continue;
+                };
+                ev_tower_build.send(TowerBuildEvent::Dispatch {
+                    entity: base,
+                    kind: TowerType(kind.clone()),
+                    pos: *position,
+                });
+            }
+            ReplayCommand::Upgrade {
+                position,
+                effect,
+                side_effect,
+            } => {
+                let Some(entity) = tower_at(&towers, *position) else {
+                    continue;
+                };
+                ev_tower_build.send(TowerBuildEvent::Upgrade {
+                    entity,
+                    effect: *effect,
+                    side_effect: *side_effect,
+                });
+            }
+            ReplayCommand::SetTargeting { position, mode } => {
+                let Some(entity) = tower_at(&towers, *position) else {
+                    continue;
+                };
+                ev_tower_build.send(TowerBuildEvent::SetTargeting {
+                    entity,
+                    mode: *mode,
+                });
+            }
+            ReplayCommand::Sell { position } => {
+                let Some(entity) = tower_at(&towers, *position) else {
+                    continue;
+                };
+                ev_tower_build.send(TowerBuildEvent::Sell { entity });
+            }
+            ReplayCommand::Relocate { position, to } => {
+                let Some(entity) = tower_at(&towers, *position) else {
+                    continue;
+                };
+                let Some(base) = tower_base_at(&tower_bases, *to) else {
+                    warn!("Replay diverged, no tower base at {}", to);
+                    continue;
+                };
+                ev_tower_build.send(TowerBuildEvent::Relocate { entity, base });
+            }
+            ReplayCommand::StartWave => {
+                ev_state_update.send(StateUpdateEvent::StartWave)
+            }
+            ReplayCommand::Heal => ev_state_update.send(StateUpdateEvent::Heal),
+        }
+    }
+}
+
+fn tower_at(
+    towers: &Query<(Entity, &Transform), With<Tower>>,
+    position: Vec3,
+) -> Option<Entity> {
+    let tower = towers.iter().find(|(_, transform)| {
+        transform.translation.distance(position) <= POSITION_TOLERANCE
+    });
+    if tower.is_none() {
+        warn!("Replay diverged, no tower at {}", position);
+    }
+    tower.map(|(entity, _)| entity)
+}
+
+fn tower_base_at(
+    tower_bases: &Query<(Entity, &GlobalTransform), With<TowerBase>>,
+    position: Vec3,
+) -> Option<Entity> {
+    tower_bases
+        .iter()
+        .find(|(_, transform)| {
+            transform.translation().distance(position) <= POSITION_TOLERANCE
+        })
+        .map(|(entity, _)| entity)
+}
+
+fn replay_event_handler(
+    mut commands: Commands,
+    mut ev_replay: EventReader<ReplayEvent>,
+    state: Res<State<GameState>>,
+    slot: Res<ReplaySlot>,
+    recorder: Res<ReplayRecorder>,
+) {
+    for event in ev_replay.iter() {
+        match event {
+            ReplayEvent::Save => {
+                if !recorder.recording {
+                    warn!("Levels loaded from a save game can't be replayed");
+                    continue;
+                }
+                match recorder.replay.write(&slot) {
+                    Ok(()) => info!("Saved the replay to {}", slot.0),
+                    Err(e) => error!("Failed to save to {}: {}", slot.0, e),
+                }
+            }
+            ReplayEvent::Play => {
+                if state.0 != GameState::MainMenu {
+                    warn!("Replays can only be played from the main menu");
+                    continue;
+                }
+                match Replay::read(&slot) {
+                    Ok(replay) => {
+                        commands.insert_resource(ReplayPlayer::new(replay))
+                    }
+                    Err(e) => error!("Failed to load {}: {}", slot.0, e),
+                }
+            }
+        }
+    }
+}
+
+fn start_replay(
+    mut player: ResMut<ReplayPlayer>,
+    mut ev_level_writer: EventWriter<LevelEvent>,
+) {
+    if !player.started {
+        info!("Playing a replay of level {}", player.replay.level);
+        ev_level_writer.send(LevelEvent::Start(player.replay.level.clone()));
+        player.started = true;
+    }
+}
+
+/// Leaving the level stops a replay that was played.
+fn stop_replay(
+    mut commands: Commands,
+    player: Option<ResMut<ReplayPlayer>>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(mut player) = player else {
+        return;
+    };
+    if player.started {
+        restore_time(&mut player, &mut strategy);
+        commands.remove_resource::<ReplayPlayer>();
+    }
+}
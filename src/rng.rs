@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::GameState;
+
+/// The only source of randomness of the game, every random draw has to use
+/// it so a level plays out the same for the same seed and inputs.
+#[derive(Resource, Clone, Debug)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of 32 bit words drawn since seeding.
+    pub fn word_pos(&self) -> u64 {
+        // Far more words than a game ever draws.
+        self.rng.get_word_pos() as u64
+    }
+
+    /// The generator with `seed` after `word_pos` words were drawn.
+    pub fn restore(seed: u64, word_pos: u64) -> Self {
+        let mut rng = Self::from_seed(seed);
+        rng.rng.set_word_pos(word_pos as u128);
+        rng
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Seed the [`GameRng`] is reset to whenever a level starts, without one a
+/// random seed is picked and logged.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct RngSeed(pub Option<u64>);
+
+pub fn rng_plugin(app: &mut App) {
+    let seed = std::env::var("TOWERISH_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok());
+    app.insert_resource(RngSeed(seed))
+        .insert_resource(GameRng::from_seed(0))
+        .add_system(seed_rng.in_schedule(OnExit(GameState::MainMenu)));
+}
+
+pub(crate) fn seed_rng(mut rng: ResMut<GameRng>, seed: Res<RngSeed>) {
+    let seed = seed.0.unwrap_or_else(|| rand::thread_rng().gen());
+    info!("Seeding the game with {}", seed);
+    *rng = GameRng::from_seed(seed);
+}
@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pathmanager::PathManager, spawn_tower, Bank, BuiltOn, CurrentLevel,
+    GameAssets, GameRng, GameState, LevelEvent, LevelLoadFailure, PlayerBase,
+    TargetingMode, Tower, TowerBase, TowerCatalog, TowerSideEffects, TowerType,
+    TowerUpgrades, UiState, WaveState,
+};
+
+/// Everything needed to continue a level between two waves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    /// Name of the [`crate::LevelDefinition`] that is played.
+    pub level: String,
+    pub money: f32,
+    pub health: f32,
+    pub enemies_killed: i32,
+    pub waves_finished: i32,
+    /// The next wave, starting at 1.
+    pub wave_count: i32,
+    pub towers: Vec<TowerSave>,
+    /// Seed of the [`GameRng`] and how many words were drawn from it.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub rng_word_pos: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TowerSave {
+    /// Id of the [`crate::TowerDefinition`] the tower was built from.
+    pub kind: String,
+    pub position: Vec3,
+    pub upgrades: Vec<TowerUpgrades>,
+    pub side_effects: Vec<TowerSideEffects>,
+    #[serde(default)]
+    pub targeting: TargetingMode,
+    /// Money spent on the tower, part of which selling it refunds.
+    #[serde(default)]
+    pub spent: f32,
+}
+
+impl SaveGame {
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(text: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(text)?)
+    }
+
+    /// Writes the save game to `slot`, a file on native and a `localStorage`
+    /// entry in the browser.
+    pub fn write(&self, slot: &SaveSlot) -> anyhow::Result<()> {
+        storage::write(&slot.0, &self.to_ron()?)
+    }
+
+    pub fn read(slot: &SaveSlot) -> anyhow::Result<Self> {
+        Self::from_ron(&storage::read(&slot.0)?)
+    }
+}
+
+/// Where the game is saved to, the path of a RON file on native and the key
+/// of a `localStorage` entry in the browser.
+#[derive(Resource, Debug, Clone)]
+pub struct SaveSlot(pub String);
+
+impl Default for SaveSlot {
+    fn default() -> Self {
+        Self("savegame.ron".to_string())
+    }
+}
+
+pub enum SaveEvent {
+    /// Saves the level that is played, only possible between waves.
+    Save,
+    /// Leaves the level that is played and continues the saved one.
+    Load,
+}
+
+/// A save game that is restored once its level is started and its map is
+/// spawned.
+#[derive(Resource)]
+pub(crate) struct PendingLoad(SaveGame);
+
+pub fn save_plugin(app: &mut App) {
+    app.init_resource::<SaveSlot>()
+        .add_event::<SaveEvent>()
+        .add_system(save_event_handler)
+        .add_system(
+            start_pending_load.in_schedule(OnEnter(GameState::MainMenu)),
+        )
+        .add_system(
+            apply_pending_load
+                .run_if(in_state(GameState::Building))
+                .run_if(resource_exists::<PendingLoad>()),
+        );
+}
+
+fn save_event_handler(
+    mut commands: Commands,
+    mut ev_save: EventReader<SaveEvent>,
+    mut ev_level_writer: EventWriter<LevelEvent>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    slot: Res<SaveSlot>,
+    level: Option<Res<CurrentLevel>>,
+    ui_state: Res<UiState>,
+    bank: Res<Bank>,
+    base: Res<PlayerBase>,
+    wave_state: Res<WaveState>,
+    towers: Query<(&Tower, &TowerType, &TargetingMode, &Transform)>,
+    rng: Res<GameRng>,
+) {
+    for event in ev_save.iter() {
+        match event {
+            SaveEvent::Save => {
+                let Some(level) = &level else {
+                    warn!("No level to save");
+                    continue;
+                };
+                if state.0 != GameState::Building {
+                    warn!("The game can only be saved between waves");
+                    continue;
+                }
+                let save = SaveGame {
+                    level: level.definition.name.clone(),
+                    money: bank.balance(),
+                    health: base.health(),
+                    enemies_killed: ui_state.enemies_killed,
+                    waves_finished: ui_state.waves_finished,
+                    wave_count: wave_state.wave_count,
+                    towers: towers
+                        .iter()
+                        .map(|(tower, kind, targeting, transform)| TowerSave {
+                            kind: kind.0.clone(),
+                            position: transform.translation,
+                            upgrades: tower.upgrades.clone(),
+                            side_effects: tower.side_effects.clone(),
+                            targeting: *targeting,
+                            spent: tower.spent,
+                        })
+                        .collect(),
+                    seed: rng.seed(),
+                    rng_word_pos: rng.word_pos(),
+                };
+                match save.write(&slot) {
+                    Ok(()) => info!("Saved the game to {}", slot.0),
+                    Err(e) => error!("Failed to save to {}: {}", slot.0, e),
+                }
+            }
+            SaveEvent::Load => {
+                let save = match SaveGame::read(&slot) {
+                    Ok(save) => save,
+                    Err(e) => {
+                        error!("Failed to load {}: {}", slot.0, e);
+                        continue;
+                    }
+                };
+                info!("Loading level {} from {}", save.level, slot.0);
+                match state.0 {
+                    GameState::MainMenu => ev_level_writer
+                        .send(LevelEvent::Start(save.level.clone())),
+                    GameState::Loading => {
+                        warn!("Wait for loading to finish before loading");
+                        continue;
+                    }
+                    _ => next_state.set(GameState::MainMenu),
+                }
+                commands.insert_resource(PendingLoad(save));
+            }
+        }
+    }
+}
+
+/// Restarts the saved level after leaving the one that was played, unless
+/// the saved level failed to load.
+fn start_pending_load(
+    mut commands: Commands,
+    pending: Option<Res<PendingLoad>>,
+    failure: Option<Res<LevelLoadFailure>>,
+    mut ev_level_writer: EventWriter<LevelEvent>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+    if failure.is_some_and(|failure| failure.level == pending.0.level) {
+        warn!("Dropping save game of level {}", pending.0.level);
+        commands.remove_resource::<PendingLoad>();
+        return;
+    }
+    ev_level_writer.send(LevelEvent::Start(pending.0.level.clone()));
+}
+
+/// Distance between a saved tower and the tower base it was built on.
+const TOWER_BASE_TOLERANCE: f32 = 0.1;
+
+fn apply_pending_load(
+    mut commands: Commands,
+    pending: Res<PendingLoad>,
+    level: Res<CurrentLevel>,
+    maps: Query<(), With<PathManager>>,
+    tower_bases: Query<(Entity, &TowerBase, &GlobalTransform)>,
+    assets: Res<GameAssets>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+    mut ui_state: ResMut<UiState>,
+    mut bank: ResMut<Bank>,
+    mut base: ResMut<PlayerBase>,
+    mut wave_state: ResMut<WaveState>,
+) {
+    let save = &pending.0;
+    if level.definition.name != save.level {
+        warn!("Dropping save game of level {}", save.level);
+        commands.remove_resource::<PendingLoad>();
+        return;
+    }
+    // Towers replace the tower bases of the map.
+    let Some(catalog) = tower_catalogs.get(&assets.towers) else {
+        return;
+    };
+    if maps.is_empty() {
+        return;
+    }
+
+    *bank = Bank::new(save.money, save.wave_count);
+    *base = PlayerBase::new(save.health);
+    ui_state.enemies_killed = save.enemies_killed;
+    ui_state.waves_finished = save.waves_finished;
+    wave_state.wave_count = save.wave_count;
+    commands.insert_resource(GameRng::restore(save.seed, save.rng_word_pos));
+
+    for saved in &save.towers {
+        let kind = TowerType(saved.kind.clone());
+        let Some(definition) = catalog.get(&kind) else {
+            warn!("No tower definition for {} found", kind);
+            continue;
+        };
+        let tower =
+            spawn_tower(&mut commands, &assets, saved.position, definition);
+        for (entity, base, transform) in &tower_bases {
+            if transform.translation().distance(saved.position)
+                <= TOWER_BASE_TOLERANCE
+            {
+                commands.entity(entity).despawn_recursive();
+                commands.entity(tower).insert(BuiltOn {
+                    base: base.clone(),
+                    transform: transform.compute_transform(),
+                });
+            }
+        }
+        let mut restored = Tower::new(definition);
+        restored.upgrades = saved.upgrades.clone();
+        restored.side_effects = saved.side_effects.clone();
+        restored.spent = saved.spent;
+        restored.update_shooting_timer(definition);
+        commands.entity(tower).insert((restored, saved.targeting));
+    }
+    info!("Loaded wave {} of level {}", save.wave_count, save.level);
+    commands.remove_resource::<PendingLoad>();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod storage {
+    pub fn write(slot: &str, text: &str) -> anyhow::Result<()> {
+        Ok(std::fs::write(slot, text)?)
+    }
+
+    pub fn read(slot: &str) -> anyhow::Result<String> {
+        Ok(std::fs::read_to_string(slot)?)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) mod storage {
+    use anyhow::anyhow;
+
+    fn local_storage() -> anyhow::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| anyhow!("localStorage is not available"))
+    }
+
+    pub fn write(slot: &str, text: &str) -> anyhow::Result<()> {
+        local_storage()?
+            .set_item(slot, text)
+            .map_err(|e| anyhow!("{:?}", e))
+    }
+
+    pub fn read(slot: &str) -> anyhow::Result<String> {
+        local_storage()?
+            .get_item(slot)
+            .map_err(|e| anyhow!("{:?}", e))?
+            .ok_or_else(|| anyhow!("nothing saved"))
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+use crate::{DamageEvent, GameState};
+
+/// Lasting effect of a hit on an enemy.
+#[derive(Debug, Reflect, Copy, Clone, PartialEq)]
+pub enum StatusEffect {
+    /// Fraction of the speed the enemy loses, only the strongest slow
+    /// applies.
+    Slow(f32),
+    /// Damage per second, hits refresh a burn instead of stacking it.
+    Burn(f32),
+    /// The enemy does not move.
+    Stun,
+    /// Damage per second, every hit adds another dose.
+    Poison(f32),
+}
+
+#[derive(Debug, Reflect, Copy, Clone, PartialEq)]
+pub struct TimedStatusEffect {
+    pub effect: StatusEffect,
+    /// Seconds the effect lasts, or is left to last on an enemy.
+    pub seconds: f32,
+}
+
+/// The status effects an enemy suffers from, ticked every frame of a wave.
+#[derive(Debug, Reflect, Component, Default, Clone)]
+pub struct StatusEffects {
+    #[reflect(ignore)]
+    effects: Vec<TimedStatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, status: TimedStatusEffect) {
+        let same_kind = self.effects.iter_mut().find(|applied| {
+            std::mem::discriminant(&applied.effect)
+                == std::mem::discriminant(&status.effect)
+        });
+        match (status.effect, same_kind) {
+            (StatusEffect::Poison(_), _) | (_, None) => {
+                self.effects.push(status)
+            }
+            (StatusEffect::Slow(strength), Some(applied)) => {
+                if let StatusEffect::Slow(applied_strength) = applied.effect {
+                    if strength >= applied_strength {
+                        *applied = status;
+                    }
+                }
+            }
+            (StatusEffect::Burn(_) | StatusEffect::Stun, Some(applied)) => {
+                *applied = status;
+            }
+        }
+    }
+
+    pub fn effects(&self) -> &[TimedStatusEffect] {
+        &self.effects
+    }
+
+    /// Factor the speed of the enemy is multiplied with.
+    pub fn speed_multiplier(&self) -> f32 {
+        self.effects.iter().fold(1.0, |multiplier, status| {
+            match status.effect {
+                StatusEffect::Slow(strength) => {
+                    f32::min(multiplier, 1.0 - strength.clamp(0.0, 1.0))
+                }
+                StatusEffect::Stun => 0.0,
+                StatusEffect::Burn(_) | StatusEffect::Poison(_) => multiplier,
+            }
+        })
+    }
+
+    /// Health lost per second, armor and resistances do not apply.
+    pub fn damage_per_second(&self) -> f32 {
+        self.effects
+            .iter()
+            .map(|status| match status.effect {
+                StatusEffect::Burn(dps) | StatusEffect::Poison(dps) => dps,
+                StatusEffect::Slow(_) | StatusEffect::Stun => 0.0,
+            })
+            .sum()
+    }
+
+    /// Runs the effects for `seconds` and drops the ones that wore off.
+    pub fn tick(&mut self, seconds: f32) {
+        for status in &mut self.effects {
+            status.seconds -= seconds;
+        }
+        self.effects.retain(|status| status.seconds > 0.0);
+    }
+}
+
+pub fn status_plugin(app: &mut App) {
+    app.register_type::<StatusEffects>().add_system(
+        tick_status_effects.run_if(in_state(GameState::WaveRunning)),
+    );
+}
+
+/// Deals the damage over time and ends effects that wore off. Kills by
+/// damage over time are not credited to a tower.
+fn tick_status_effects(
+    mut enemies: Query<(Entity, &mut StatusEffects)>,
+    mut ev_damage: EventWriter<DamageEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut status_effects) in &mut enemies {
+        if status_effects.effects.is_empty() {
+            continue;
+        }
+        let dps = status_effects.damage_per_second();
+        if dps > 0.0 {
+            ev_damage.send(DamageEvent {
+                entity,
+                amount: dps * time.delta_seconds(),
+                tower: None,
+            });
+        }
+        status_effects.tick(time.delta_seconds());
+    }
+}
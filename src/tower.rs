@@ -1,12 +1,26 @@
-use std::time::Duration;
+use std::{f32::consts::TAU, time::Duration};
 
-use bevy::{prelude::*, utils::FloatOrd};
+use bevy::{
+    asset::{AssetPath, LoadContext},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::FloatOrd,
+};
 use bevy_mod_picking::*;
+use bevy_rapier3d::prelude::*;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use serde::{Deserialize, Serialize};
 use strum::{Display as EnumDisplay, EnumIter};
 
 use crate::{
-    graphics::CreateParticleSystem, Enemy, GameAssets, Lifetime, PhysicsBundle,
-    Projectile,
+    graphics::CreateParticleSystem, pathmanager::PathManager,
+    world::tower_base_bundle, Bank, DamageType, Enemy, EnemyDied, GameAssets,
+    GameRng, GameState, Health, Lifetime, PathProgress, PhysicsBundle,
+    Projectile, RonAsset, RonAssetLoader, StatusEffect, TimedStatusEffect,
+    TowerBase, TransactionSource, UiState,
 };
 
 #[derive(Component)]
@@ -18,6 +32,86 @@ pub struct Tower {
     pub bullet_offset: Vec3,
     pub upgrades: Vec<TowerUpgrades>,
     pub side_effects: Vec<TowerSideEffects>,
+    /// Seconds until a jammed tower shoots again.
+    pub jammed: f32,
+    /// Money spent on building and upgrading the tower.
+    pub spent: f32,
+    /// Enemies the tower dealt the final blow to.
+    pub kills: u32,
+}
+
+impl Tower {
+    /// A freshly built tower without upgrades.
+    pub fn new(definition: &TowerDefinition) -> Self {
+        Self {
+            shooting_timer: Timer::new(
+                definition.shooting_duration(&[]),
+                TimerMode::Repeating,
+            ),
+            bullet_offset: Vec3::new(0.0, 1.2, 0.0),
+            upgrades: vec![],
+            side_effects: vec![],
+            jammed: 0.0,
+            spent: 0.0,
+            kills: 0,
+        }
+    }
+
+    /// Money the player gets back for selling the tower.
+    pub fn refund(&self) -> f32 {
+        self.spent * SELL_REFUND
+    }
+
+    /// The status effects the hits of the tower apply, the forces of
+    /// upgrades of the same kind add up.
+    pub fn status_effects(&self) -> Vec<TimedStatusEffect> {
+        let mut combined: Vec<TowerUpgrades> = vec![];
+        for upgrade in &self.upgrades {
+            if upgrade.status_effect().is_none() {
+                continue;
+            }
+            let same_kind = combined.iter_mut().find(|combined| {
+                std::mem::discriminant(*combined)
+                    == std::mem::discriminant(upgrade)
+            });
+            match same_kind {
+                Some(combined) => {
+                    *combined =
+                        combined.set_force(combined.force() + upgrade.force())
+                }
+                None => combined.push(*upgrade),
+            }
+        }
+        combined
+            .iter()
+            .filter_map(TowerUpgrades::status_effect)
+            .collect()
+    }
+
+    /// Applies the shooting interval of the upgrades and side effects.
+    pub fn update_shooting_timer(&mut self, definition: &TowerDefinition) {
+        let duration = self.side_effects.iter().fold(
+            definition.shooting_duration(&self.upgrades),
+            |duration, side_effect| match side_effect {
+                TowerSideEffects::SlowFire(v) => {
+                    duration + Duration::from_secs_f32(v / 10.0)
+                }
+                _ => duration,
+            },
+        );
+        self.shooting_timer.set_duration(duration);
+    }
+}
+
+/// Share of the money spent on a tower that selling it refunds.
+pub const SELL_REFUND: f32 = 0.7;
+
+/// The tower base a tower was built on, which is restored when the tower is
+/// sold or moved away.
+#[derive(Component, Clone)]
+pub struct BuiltOn {
+    pub base: TowerBase,
+    pub transform: Transform,
 }
 
 #[derive(Component, Clone)]
@@ -26,32 +120,133 @@ pub struct SideEffectBundle {
 }
 
 impl SideEffectBundle {
+    /// The side effects of `tower` that projectiles carry to their hits.
     pub fn from_tower(tower: &Tower) -> Self {
         Self {
-            side_effects: tower.side_effects.clone(),
+            side_effects: tower
+                .side_effects
+                .iter()
+                .filter(|side_effect| side_effect.applies_on_hit())
+                .copied()
+                .collect(),
         }
     }
 }
 
-#[derive(Debug, Reflect, Component, EnumIter, EnumDisplay, Copy, Clone)]
-pub enum TowerType {
-    Gun,
-    Rocket,
-    Sniper,
+/// Id of the [`TowerDefinition`] a tower was built from.
+#[derive(Debug, Reflect, Component, Clone, PartialEq, Eq, Hash)]
+pub struct TowerType(pub String);
+
+impl std::fmt::Display for TowerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// All towers the player can build, loaded from a `*.towers.ron` file.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "98f259bd-6575-4c91-83d6-b706ed047ebc"]
+pub struct TowerCatalog {
+    pub towers: Vec<TowerDefinition>,
+}
+
+impl TowerCatalog {
+    pub fn get(&self, kind: &TowerType) -> Option<&TowerDefinition> {
+        self.towers.iter().find(|tower| tower.id == kind.0)
+    }
+
+    /// The projectile scenes of all towers.
+    pub fn dependencies(&self) -> Vec<HandleUntyped> {
+        self.towers
+            .iter()
+            .map(|tower| tower.projectile.scene_handle.clone_untyped())
+            .collect()
+    }
+}
+
+impl RonAsset for TowerCatalog {
+    const EXTENSIONS: &'static [&'static str] = &["towers.ron"];
+
+    fn resolve(
+        &mut self,
+        load_context: &LoadContext,
+    ) -> Vec<AssetPath<'static>> {
+        self.towers
+            .iter_mut()
+            .map(|tower| {
+                let projectile = &mut tower.projectile;
+                projectile.scene_handle =
+                    load_context.get_handle(projectile.scene.as_str());
+                AssetPath::from(projectile.scene.as_str()).to_owned()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TowerDefinition {
+    pub id: String,
+    pub name: String,
+    pub price: f32,
+    /// Seconds between two shots before any upgrades.
+    pub shooting_interval: f32,
+    /// Distance to the enemies the tower shoots at before any upgrades.
+    pub range: f32,
+    /// Whether map colliders between the tower and an enemy block the shot.
+    #[serde(default)]
+    pub line_of_sight: bool,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    pub projectile: ProjectileDefinition,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProjectileDefinition {
+    pub scene: String,
+    #[serde(skip)]
+    pub scene_handle: Handle<Scene>,
+    pub speed: f32,
+    pub force: f32,
+    /// Seconds until the projectile despawns without hitting anything.
+    pub lifetime: f32,
+    /// Homing projectiles follow their target, the others fly straight
+    /// towards where the target was when shot.
+    pub homing: bool,
 }
 
-impl TowerType {
+impl TowerDefinition {
+    pub fn kind(&self) -> TowerType {
+        TowerType(self.id.clone())
+    }
+
     pub fn get_price(&self, wave_multiplier: i32) -> f32 {
         let wave_multiplier = if wave_multiplier <= 0 {
             1
         } else {
             wave_multiplier
         };
-        match self {
-            TowerType::Gun => 500.0 * wave_multiplier as f32,
-            TowerType::Rocket => 650.0 * wave_multiplier as f32,
-            TowerType::Sniper => 600.0 * wave_multiplier as f32,
-        }
+        self.price * wave_multiplier as f32
+    }
+
+    pub fn range(&self, upgrades: &[TowerUpgrades]) -> f32 {
+        upgrades
+            .iter()
+            .fold(self.range, |range, upgrade| match upgrade {
+                TowerUpgrades::RangeBuff(v) => range + v,
+                _ => range,
+            })
+    }
+
+    pub fn shooting_duration(&self, upgrades: &[TowerUpgrades]) -> Duration {
+        upgrades.iter().fold(
+            Duration::from_secs_f32(self.shooting_interval),
+            |duration, upgrade| match upgrade {
+                TowerUpgrades::ShootingSpeedBuff(v) => {
+                    duration.saturating_sub(Duration::from_secs_f32(v / 10.0))
+                }
+                _ => duration,
+            },
+        )
     }
 }
 
@@ -64,16 +259,130 @@ pub enum TowerBuildEvent {
     Upgrade {
         entity: Entity,
         effect: TowerUpgrades,
-        side_effect: Option<TowerSideEffects>,
+        side_effect: UpgradeSideEffect,
     },
+    SetTargeting {
+        entity: Entity,
+        mode: TargetingMode,
+    },
+    /// Removes the tower for a [`Tower::refund`] and restores its base.
+    Sell {
+        entity: Entity,
+    },
+    /// Moves the tower onto the empty tower base `base`.
+    Relocate {
+        entity: Entity,
+        base: Entity,
+    },
+}
+
+/// The side effect an upgrade comes with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UpgradeSideEffect {
+    /// Drawn from the [`GameRng`] when the upgrade is applied.
+    Roll,
+    /// Picked by the sender, e.g. to set up a tower.
+    Fixed(Option<TowerSideEffects>),
+}
+
+impl TowerBuildEvent {
+    /// Money the event costs, `None` for events that are free or refunded.
+    pub fn price(
+        &self,
+        catalog: Option<&TowerCatalog>,
+        wave_multiplier: i32,
+    ) -> Option<f32> {
+        match self {
+            TowerBuildEvent::Dispatch { kind, .. } => catalog
+                .and_then(|c| c.get(kind))
+                .map(|definition| definition.get_price(wave_multiplier)),
+            TowerBuildEvent::Upgrade { effect, .. } => {
+                Some(effect.get_price(wave_multiplier, effect.force() as i32))
+            }
+            TowerBuildEvent::SetTargeting { .. }
+            | TowerBuildEvent::Sell { .. }
+            | TowerBuildEvent::Relocate { .. } => None,
+        }
+    }
+}
+
+/// Which enemy in reach a tower shoots at.
+#[derive(
+    Debug,
+    Default,
+    Reflect,
+    Component,
+    EnumIter,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    EnumDisplay,
+    Serialize,
+    Deserialize,
+)]
+pub enum TargetingMode {
+    /// The enemy furthest along its route.
+    First,
+    /// The enemy that is the least far along its route.
+    Last,
+    /// The enemy with the most health.
+    Strongest,
+    /// The enemy with the least health.
+    Weakest,
+    #[default]
+    Closest,
 }
 
-#[derive(Debug, Reflect, Component, EnumIter, Copy, Clone, EnumDisplay)]
+impl TargetingMode {
+    /// The target out of `enemies` with their position, distance left to
+    /// the end of their route and health for a tower at `origin`.
+    pub fn pick(
+        self,
+        origin: Vec3,
+        enemies: impl IntoIterator<Item = (Entity, Vec3, f32, f32)>,
+    ) -> Option<(Entity, Vec3)> {
+        enemies
+            .into_iter()
+            .max_by_key(|(_, position, remaining, health)| {
+                FloatOrd(match self {
+                    TargetingMode::First => -remaining,
+                    TargetingMode::Last => *remaining,
+                    TargetingMode::Strongest => *health,
+                    TargetingMode::Weakest => -health,
+                    TargetingMode::Closest => -position.distance(origin),
+                })
+            })
+            .map(|(entity, position, _, _)| (entity, position))
+    }
+}
+
+#[derive(
+    Debug,
+    Reflect,
+    Component,
+    EnumIter,
+    Copy,
+    Clone,
+    PartialEq,
+    EnumDisplay,
+    Serialize,
+    Deserialize,
+)]
 pub enum TowerUpgrades {
     BulletSpeedBuff(f32),
     ForceBuff(f32),
     ShootingSpeedBuff(f32),
     AOE(f32),
+    RangeBuff(f32),
+    /// Hits slow enemies down.
+    FrostRounds(f32),
+    /// Hits set enemies on fire.
+    Incendiary(f32),
+    /// Hits stun enemies for a moment.
+    ConcussionRounds(f32),
+    /// Hits poison enemies, doses of several hits add up.
+    ToxicRounds(f32),
 }
 
 impl TowerUpgrades {
@@ -90,14 +399,36 @@ impl TowerUpgrades {
             TowerUpgrades::ForceBuff(_) => {
                 100. + (1.05 * (wave_multiplier as f32 * force as f32))
             }
-            TowerUpgrades::AOE(_) => {
+            TowerUpgrades::AOE(_) | TowerUpgrades::RangeBuff(_) => {
                 200. + (2.05 * (wave_multiplier as f32 * force as f32))
             }
             TowerUpgrades::ShootingSpeedBuff(_) => {
                 500. + (5.05 * (wave_multiplier as f32 * force as f32))
             }
+            TowerUpgrades::FrostRounds(_)
+            | TowerUpgrades::Incendiary(_)
+            | TowerUpgrades::ToxicRounds(_) => {
+                150. + (1.55 * (wave_multiplier as f32 * force as f32))
+            }
+            TowerUpgrades::ConcussionRounds(_) => {
+                300. + (3.05 * (wave_multiplier as f32 * force as f32))
+            }
         }
     }
+    pub fn force(&self) -> f32 {
+        match self {
+            TowerUpgrades::BulletSpeedBuff(force)
+            | TowerUpgrades::ForceBuff(force)
+            | TowerUpgrades::ShootingSpeedBuff(force)
+            | TowerUpgrades::AOE(force)
+            | TowerUpgrades::RangeBuff(force)
+            | TowerUpgrades::FrostRounds(force)
+            | TowerUpgrades::Incendiary(force)
+            | TowerUpgrades::ConcussionRounds(force)
+            | TowerUpgrades::ToxicRounds(force) => *force,
+        }
+    }
+
     pub fn set_force(self, force: f32) -> Self {
         match self {
             TowerUpgrades::BulletSpeedBuff(_) => Self::BulletSpeedBuff(force),
@@ -106,17 +437,126 @@ impl TowerUpgrades {
                 Self::ShootingSpeedBuff(force)
             }
             TowerUpgrades::AOE(_) => Self::AOE(force),
+            TowerUpgrades::RangeBuff(_) => Self::RangeBuff(force),
+            TowerUpgrades::FrostRounds(_) => Self::FrostRounds(force),
+            TowerUpgrades::Incendiary(_) => Self::Incendiary(force),
+            TowerUpgrades::ConcussionRounds(_) => Self::ConcussionRounds(force),
+            TowerUpgrades::ToxicRounds(_) => Self::ToxicRounds(force),
         }
     }
+
+    /// The status effect hits of a tower with this upgrade apply.
+    pub fn status_effect(&self) -> Option<TimedStatusEffect> {
+        let (effect, seconds) = match *self {
+            TowerUpgrades::FrostRounds(force) => {
+                (StatusEffect::Slow(force / (force + 10.0)), 2.0)
+            }
+            TowerUpgrades::Incendiary(force) => {
+                (StatusEffect::Burn(0.05 * force), 3.0)
+            }
+            TowerUpgrades::ConcussionRounds(force) => {
+                (StatusEffect::Stun, (0.02 * force).min(0.5))
+            }
+            TowerUpgrades::ToxicRounds(force) => {
+                (StatusEffect::Poison(0.02 * force), 5.0)
+            }
+            TowerUpgrades::BulletSpeedBuff(_)
+            | TowerUpgrades::ForceBuff(_)
+            | TowerUpgrades::ShootingSpeedBuff(_)
+            | TowerUpgrades::AOE(_)
+            | TowerUpgrades::RangeBuff(_) => return None,
+        };
+        Some(TimedStatusEffect { effect, seconds })
+    }
 }
 
-#[derive(Debug, Reflect, Component, EnumIter, Copy, Clone)]
+#[derive(
+    Debug,
+    Reflect,
+    Component,
+    EnumIter,
+    Copy,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
 pub enum TowerSideEffects {
+    // Applied to the enemy on every hit by `hit_event_handler`.
+    /// Hits lose this much force.
     WeakShot(f32),
+    /// Hits heal the enemy by this much.
     HealShot(f32),
+
+    // Applied by `tower_shoot` on every shot of the tower.
+    /// Adds a tenth of the value in seconds to the shooting interval.
+    SlowFire(f32),
+    /// Percent of the shots that fly off in a random direction.
+    Misfire(f32),
+    /// Seconds the tower stops shooting for when it jams, which happens on
+    /// [`JAM_CHANCE`] of the shots.
+    Jam(f32),
+
+    // Applied once by `tower_build` when the upgrade they came with is built.
+    /// Takes back the upgrade built before, or the new one on towers
+    /// without other upgrades.
+    RevertUpgrade,
+    /// Towers within [`FRIENDLY_FIRE_RADIUS`] lose their newest upgrade.
+    FriendlyFire,
+    /// Destroys the tower.
+    Explode,
 }
 
+/// Share of the shots a tower with [`TowerSideEffects::Jam`] jams on.
+pub const JAM_CHANCE: f32 = 0.05;
+
+/// Distance of the towers hit by [`TowerSideEffects::FriendlyFire`].
+pub const FRIENDLY_FIRE_RADIUS: f32 = 5.0;
+
 impl TowerSideEffects {
+    /// Draws the side effect an upgrade with `force` comes with, the greedier
+    /// the player and the later the wave the more likely there is one.
+    /// Negative forces count as no force at all.
+    pub fn roll(
+        rng: &mut GameRng,
+        wave_multiplier: i32,
+        force: i32,
+    ) -> Option<TowerSideEffects> {
+        let force = force.max(0);
+        let weights = match WeightedIndex::new(TowerSideEffects::get_weights(
+            wave_multiplier,
+            force,
+        )) {
+            Ok(weights) => weights,
+            Err(e) => {
+                warn!("Could not roll a side effect: {}", e);
+                return None;
+            }
+        };
+        let force = force as f32;
+        let options = [
+            None,
+            Some(TowerSideEffects::WeakShot(force)),
+            Some(TowerSideEffects::HealShot(force)),
+            Some(TowerSideEffects::SlowFire(force)),
+            Some(TowerSideEffects::Misfire(force)),
+            Some(TowerSideEffects::Jam(force / 2.0)),
+            Some(TowerSideEffects::RevertUpgrade),
+            Some(TowerSideEffects::FriendlyFire),
+            Some(TowerSideEffects::Explode),
+        ];
+        options[weights.sample(rng)]
+    }
+
+    /// Whether projectiles carry the side effect to the enemies they hit,
+    /// the others affect the tower itself.
+    pub fn applies_on_hit(&self) -> bool {
+        matches!(
+            self,
+            TowerSideEffects::WeakShot(_) | TowerSideEffects::HealShot(_)
+        )
+    }
+
     pub fn get_weights(wave_multiplier: i32, force: i32) -> Vec<f32> {
         let wave_multiplier = if wave_multiplier <= 0 {
             1
@@ -124,43 +564,187 @@ impl TowerSideEffects {
             wave_multiplier
         };
 
+        // In the order of the options in `roll`
+        let greed = wave_multiplier as f32 * force as f32;
         vec![
             100.0,
-            0.5 * wave_multiplier as f32 * force as f32,
-            0.2 * wave_multiplier as f32 * force as f32,
+            0.5 * greed,
+            0.2 * greed,
+            0.3 * greed,
+            0.2 * greed,
+            0.1 * greed,
+            0.05 * greed,
+            0.05 * greed,
+            0.02 * greed,
         ]
     }
 }
 
 pub fn tower_plugin(app: &mut App) {
-    app.add_event::<TowerBuildEvent>()
+    app.register_type::<TargetingMode>()
+        .add_event::<TowerBuildEvent>()
+        .add_asset::<TowerCatalog>()
+        .init_asset_loader::<RonAssetLoader<TowerCatalog>>()
         .add_system(tower_build)
-        .add_system(tower_shoot);
+        .add_system(
+            tower_shoot
+                .after(tower_build)
+                .run_if(in_state(GameState::WaveRunning)),
+        )
+        .add_system(tower_catalog_reload)
+        .add_system(count_kills)
+        .add_startup_system(spawn_range_ring)
+        .add_system(show_range_ring);
+}
+
+/// Splash radius each force of [`TowerUpgrades::AOE`] adds to projectiles.
+pub const AOE_RADIUS_PER_FORCE: f32 = 0.5;
+
+/// Shows the range of the hovered or selected tower.
+#[derive(Component)]
+pub struct RangeRing;
+
+fn spawn_range_ring(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        SceneBundle {
+            scene: assets.ring_a.clone(),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        },
+        Name::new("Range Ring"),
+        RangeRing,
+    ));
+}
+
+/// Places the ring around the hovered tower or tower base, or the selected
+/// one when nothing is hovered. Tower bases show the furthest range any tower
+/// built on them could reach.
+fn show_range_ring(
+    mut rings: Query<(&mut Transform, &mut Visibility), With<RangeRing>>,
+    towers: Query<(
+        &GlobalTransform,
+        &Tower,
+        &TowerType,
+        Option<&Hover>,
+        Option<&Selection>,
+    )>,
+    tower_bases: Query<
+        (&GlobalTransform, Option<&Hover>, Option<&Selection>),
+        With<TowerBase>,
+    >,
+    assets: Res<GameAssets>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+) {
+    let Ok((mut ring, mut visibility)) = rings.get_single_mut() else {
+        return;
+    };
+    let Some(catalog) = tower_catalogs.get(&assets.towers) else {
+        return;
+    };
+    let tower_ranges = towers.iter().filter_map(
+        |(transform, tower, kind, hover, selection)| {
+            let range = catalog.get(kind)?.range(&tower.upgrades);
+            Some((transform, range, hover, selection))
+        },
+    );
+    let base_range = catalog
+        .towers
+        .iter()
+        .map(|definition| definition.range(&[]))
+        .fold(0.0, f32::max);
+    let base_ranges =
+        tower_bases.iter().map(|(transform, hover, selection)| {
+            (transform, base_range, hover, selection)
+        });
+    let candidates: Vec<_> = tower_ranges.chain(base_ranges).collect();
+
+    let hovered = candidates
+        .iter()
+        .find(|(_, _, hover, _)| hover.is_some_and(Hover::hovered));
+    let selected = candidates.iter().find(|(_, _, _, selection)| {
+        selection.is_some_and(Selection::selected)
+    });
+    match hovered.or(selected) {
+        Some((transform, range, _, _)) => {
+            // ring_a has a radius of 1 and lies flat at its origin
+            ring.translation = transform.translation();
+            ring.scale = Vec3::new(*range, 1.0, *range);
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
 }
 
 pub fn tower_shoot(
     mut commands: Commands,
     assets: Res<GameAssets>,
-    mut towers: Query<(Entity, &mut Tower, &TowerType, &GlobalTransform)>,
-    targets: Query<(Entity, &GlobalTransform), With<Enemy>>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+    mut towers: Query<(
+        Entity,
+        &mut Tower,
+        &TowerType,
+        &TargetingMode,
+        &GlobalTransform,
+    )>,
+    targets: Query<
+        (Entity, &GlobalTransform, &PathProgress, &Health),
+        With<Enemy>,
+    >,
+    paths: Query<&PathManager>,
+    rapier_context: Res<RapierContext>,
+    mut rng: ResMut<GameRng>,
     time: Res<Time>,
 ) {
-    for (tower_ent, mut tower, tower_type, transform) in &mut towers {
+    let Some(catalog) = tower_catalogs.get(&assets.towers) else {
+        return;
+    };
+    for (tower_ent, mut tower, tower_type, mode, transform) in &mut towers {
+        if tower.jammed > 0.0 {
+            tower.jammed -= time.delta_seconds();
+            continue;
+        }
         tower.shooting_timer.tick(time.delta());
         if tower.shooting_timer.just_finished() {
+            let Some(definition) = catalog.get(tower_type) else {
+                continue;
+            };
             let bullet_spawn = transform.translation() + tower.bullet_offset;
 
             let target_offset = transform.translation();
 
-            let target = targets.iter().min_by_key(|target_transform| {
-                FloatOrd(Vec3::distance(
-                    target_transform.1.translation(),
-                    bullet_spawn,
-                ))
-            });
+            let range = definition.range(&tower.upgrades);
+            let in_sight = |position: Vec3| {
+                !definition.line_of_sight
+                    || rapier_context
+                        .cast_ray(
+                            bullet_spawn,
+                            position - bullet_spawn,
+                            1.0,
+                            true,
+                            QueryFilter::only_fixed(),
+                        )
+                        .is_none()
+            };
+            let target = mode.pick(
+                bullet_spawn,
+                targets
+                    .iter()
+                    .map(|(entity, transform, progress, health)| {
+                        (
+                            entity,
+                            transform.translation(),
+                            progress.remaining(&paths).unwrap_or(f32::INFINITY),
+                            health.value,
+                        )
+                    })
+                    .filter(|(_, position, _, _)| {
+                        position.distance(bullet_spawn) <= range
+                            && in_sight(*position)
+                    }),
+            );
 
             if let Some(target) = target {
-                debug!("Shooting at target at: {}", target.1.translation());
+                debug!("Shooting at target at: {}", target.1);
 
                 let mut speed_mod = 0.;
                 let mut force_mod = 0.;
@@ -171,54 +755,70 @@ pub fn tower_shoot(
                         TowerUpgrades::BulletSpeedBuff(v) => speed_mod += v,
                         TowerUpgrades::ForceBuff(v) => force_mod += v,
                         TowerUpgrades::AOE(v) => aoe_mod += v,
-                        TowerUpgrades::ShootingSpeedBuff(_) => {}
+                        TowerUpgrades::ShootingSpeedBuff(_)
+                        | TowerUpgrades::RangeBuff(_)
+                        | TowerUpgrades::FrostRounds(_)
+                        | TowerUpgrades::Incendiary(_)
+                        | TowerUpgrades::ConcussionRounds(_)
+                        | TowerUpgrades::ToxicRounds(_) => {}
                     }
                 }
 
-                let (direction, speed, force, lifetime, handle) =
-                    match tower_type {
-                        TowerType::Gun => (
-                            Some(target.1.translation() - target_offset),
-                            60.0 + speed_mod,
-                            1.0 + force_mod,
-                            Timer::from_seconds(1.5, TimerMode::Once),
-                            assets.bullet_scene.clone(),
-                        ),
-                        TowerType::Rocket => (
-                            None,
-                            10.0 + speed_mod,
-                            10.0 + force_mod,
-                            Timer::from_seconds(10.0, TimerMode::Once),
-                            assets.rocket_scene.clone(),
-                        ),
-                        TowerType::Sniper => (
-                            None,
-                            100.0 + speed_mod,
-                            2.0 + force_mod,
-                            Timer::from_seconds(9.0, TimerMode::Once),
-                            assets.sniper_bullet_scene.clone(),
-                        ),
-                    };
+                let projectile = &definition.projectile;
+                let (mut direction, mut homing_target) = if projectile.homing {
+                    (None, Some(target.0))
+                } else {
+                    (Some(target.1 - target_offset), None)
+                };
+
+                let mut jammed = 0.0;
+                for side_effect in &tower.side_effects {
+                    match side_effect {
+                        TowerSideEffects::Misfire(percent) => {
+                            if rng.gen::<f32>() * 100.0 < *percent {
+                                let angle = rng.gen_range(0.0..TAU);
+                                direction = Some(Vec3::new(
+                                    angle.cos(),
+                                    0.0,
+                                    angle.sin(),
+                                ));
+                                homing_target = None;
+                            }
+                        }
+                        TowerSideEffects::Jam(seconds) => {
+                            if rng.gen::<f32>() < JAM_CHANCE {
+                                jammed = f32::max(jammed, *seconds);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                tower.jammed = jammed;
 
                 commands.entity(tower_ent).with_children(|commands| {
                     commands.spawn((
                         SceneBundle {
-                            scene: handle,
+                            scene: projectile.scene_handle.clone(),
                             transform: Transform::from_xyz(0.0, 0.0, 0.0)
                                 .with_scale(Vec3::new(4.0, 4.0, 4.0))
-                                .looking_at(-target.1.translation(), Vec3::Y),
+                                .looking_at(-target.1, Vec3::Y),
                             ..default()
                         },
-                        Lifetime { timer: lifetime },
+                        Lifetime {
+                            timer: Timer::from_seconds(
+                                projectile.lifetime,
+                                TimerMode::Once,
+                            ),
+                        },
                         Projectile {
                             direction,
-                            speed,
-                            force,
-                            target: match tower_type {
-                                TowerType::Gun => None,
-                                TowerType::Rocket => Some(target.0),
-                                TowerType::Sniper => Some(target.0),
-                            },
+                            speed: projectile.speed + speed_mod,
+                            force: projectile.force + force_mod,
+                            damage_type: definition.damage_type,
+                            target: homing_target,
+                            tower: Some(tower_ent),
+                            aoe_radius: aoe_mod * AOE_RADIUS_PER_FORCE,
+                            status_effects: tower.status_effects(),
                         },
                         Name::new("Bullet"),
                         PhysicsBundle::moving_entity().make_kinematic(),
@@ -234,13 +834,8 @@ pub fn spawn_tower(
     commands: &mut Commands,
     assets: &GameAssets,
     position: Vec3,
-    tt: TowerType,
+    definition: &TowerDefinition,
 ) -> Entity {
-    let shooting_timer = match tt {
-        TowerType::Gun => Timer::from_seconds(0.2, TimerMode::Repeating),
-        TowerType::Rocket => Timer::from_seconds(1.5, TimerMode::Repeating),
-        TowerType::Sniper => Timer::from_seconds(0.8, TimerMode::Repeating),
-    };
     commands
         .spawn((
             PbrBundle {
@@ -249,14 +844,10 @@ pub fn spawn_tower(
                 transform: Transform::from_translation(position),
                 ..Default::default()
             },
-            Name::new("Default Tower"),
-            Tower {
-                shooting_timer,
-                bullet_offset: Vec3::new(0.0, 1.2, 0.0),
-                upgrades: vec![],
-                side_effects: vec![],
-            },
-            tt,
+            Name::new(format!("{} Tower", definition.name)),
+            Tower::new(definition),
+            definition.kind(),
+            TargetingMode::default(),
             PickableBundle::default(),
             Highlighting {
                 initial: assets.default_collider_color.clone(),
@@ -275,18 +866,73 @@ pub fn spawn_tower(
         .id()
 }
 
-fn tower_build(
+pub(crate) fn tower_build(
     mut ev_tower_build_events: EventReader<TowerBuildEvent>,
     mut commands: Commands,
     assets: Res<GameAssets>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
     mut particle_events: EventWriter<CreateParticleSystem>,
-    mut towers: Query<&mut Tower>,
+    mut towers: Query<(
+        Entity,
+        &mut Tower,
+        &TowerType,
+        &mut Transform,
+        Option<&BuiltOn>,
+    )>,
+    tower_bases: Query<(&TowerBase, &GlobalTransform)>,
+    ui_state: Res<UiState>,
+    mut bank: ResMut<Bank>,
+    mut rng: ResMut<GameRng>,
 ) {
+    let catalog = tower_catalogs.get(&assets.towers);
+    // Towers and bases despawned by earlier events, they are still found by
+    // the queries.
+    let mut removed = vec![];
     for event in ev_tower_build_events.iter() {
+        let (entity, base) = match event {
+            TowerBuildEvent::Relocate { entity, base } => {
+                (*entity, Some(*base))
+            }
+            TowerBuildEvent::Dispatch { entity, .. }
+            | TowerBuildEvent::Upgrade { entity, .. }
+            | TowerBuildEvent::SetTargeting { entity, .. }
+            | TowerBuildEvent::Sell { entity } => (*entity, None),
+        };
+        if let Some(entity) = std::iter::once(entity)
+            .chain(base)
+            .find(|entity| removed.contains(entity))
+        {
+            warn!("{:?} was already removed", entity);
+            continue;
+        }
+        let price = event
+            .price(catalog, ui_state.waves_finished)
+            .unwrap_or_default();
         match event {
             TowerBuildEvent::Dispatch { entity, kind, pos } => {
+                let Some(definition) = catalog.and_then(|c| c.get(kind)) else {
+                    warn!("No tower definition for {} found", kind);
+                    continue;
+                };
+                if !bank.try_spend(TransactionSource::Build, price) {
+                    warn!("Not enough money to build {}", kind);
+                    continue;
+                }
+                let built_on = tower_bases.get(*entity).ok();
                 commands.entity(*entity).despawn_recursive();
-                spawn_tower(&mut commands, &assets, *pos, *kind);
+                removed.push(*entity);
+                let tower =
+                    spawn_tower(&mut commands, &assets, *pos, definition);
+                commands.entity(tower).insert(Tower {
+                    spent: price,
+                    ..Tower::new(definition)
+                });
+                if let Some((base, transform)) = built_on {
+                    commands.entity(tower).insert(BuiltOn {
+                        base: base.clone(),
+                        transform: transform.compute_transform(),
+                    });
+                }
                 particle_events.send(CreateParticleSystem {
                     system: crate::graphics::ParticleSystemType::Landing,
                     transform: Transform::from_translation(*pos),
@@ -297,26 +943,165 @@ fn tower_build(
                 effect,
                 side_effect,
             } => {
-                if let Ok(mut tower) = towers.get_mut(*entity) {
-                    match effect {
-                        TowerUpgrades::ShootingSpeedBuff(v) => {
-                            let speed_up = v / 10.0;
-                            let duration = tower
-                                .shooting_timer
-                                .duration()
-                                .clone()
-                                .saturating_sub(Duration::from_secs_f32(
-                                    speed_up,
-                                ));
-                            tower.shooting_timer.set_duration(duration);
+                let Ok((_, mut tower, kind, transform, built_on)) =
+                    towers.get_mut(*entity)
+                else {
+                    continue;
+                };
+                if !bank.try_spend(TransactionSource::Upgrade, price) {
+                    warn!("Not enough money to upgrade {:?}", entity);
+                    continue;
+                }
+                let side_effect = match side_effect {
+                    UpgradeSideEffect::Roll => TowerSideEffects::roll(
+                        &mut rng,
+                        ui_state.waves_finished,
+                        effect.force() as i32,
+                    ),
+                    UpgradeSideEffect::Fixed(side_effect) => *side_effect,
+                };
+                let position = transform.translation;
+                tower.spent += price;
+                tower.upgrades.push(*effect);
+                if let Some(side_effect) = side_effect {
+                    tower.side_effects.push(side_effect);
+                }
+                match side_effect {
+                    Some(TowerSideEffects::RevertUpgrade) => {
+                        let newest = tower.upgrades.len().saturating_sub(2);
+                        tower.upgrades.remove(newest);
+                    }
+                    Some(TowerSideEffects::Explode) => {
+                        info!("Tower {:?} exploded", entity);
+                        commands.entity(*entity).despawn_recursive();
+                        removed.push(*entity);
+                        if let Some(built_on) = built_on {
+                            commands.spawn(tower_base_bundle(
+                                &assets,
+                                built_on.base.clone(),
+                                built_on.transform,
+                            ));
                         }
-                        effect => tower.upgrades.push(*effect),
+                        particle_events.send(CreateParticleSystem {
+                            system: crate::graphics::ParticleSystemType::Impact,
+                            transform: Transform::from_translation(position),
+                        });
+                        continue;
+                    }
+                    _ => {}
+                }
+                if let Some(definition) = catalog.and_then(|c| c.get(kind)) {
+                    tower.update_shooting_timer(definition);
+                }
+
+                if side_effect != Some(TowerSideEffects::FriendlyFire) {
+                    continue;
+                }
+                for (other, mut tower, kind, transform, _) in &mut towers {
+                    if other == *entity
+                        || transform.translation.distance(position)
+                            > FRIENDLY_FIRE_RADIUS
+                    {
+                        continue;
                     }
-                    if let Some(side_effect) = side_effect {
-                        tower.side_effects.push(*side_effect);
+                    tower.upgrades.pop();
+                    if let Some(definition) = catalog.and_then(|c| c.get(kind))
+                    {
+                        tower.update_shooting_timer(definition);
                     }
                 }
             }
+            TowerBuildEvent::SetTargeting { entity, mode } => {
+                if towers.get(*entity).is_err() {
+                    warn!("Only towers have a targeting mode");
+                    continue;
+                }
+                commands.entity(*entity).insert(*mode);
+            }
+            TowerBuildEvent::Sell { entity } => {
+                let Ok((_, tower, _, _, built_on)) = towers.get(*entity) else {
+                    continue;
+                };
+                info!("Sold tower {:?} for {:.2}", entity, tower.refund());
+                bank.earn(TransactionSource::Sell, tower.refund());
+                commands.entity(*entity).despawn_recursive();
+                removed.push(*entity);
+                if let Some(built_on) = built_on {
+                    commands.spawn(tower_base_bundle(
+                        &assets,
+                        built_on.base.clone(),
+                        built_on.transform,
+                    ));
+                }
+            }
+            TowerBuildEvent::Relocate { entity, base } => {
+                let Ok((base_kind, base_transform)) = tower_bases.get(*base)
+                else {
+                    warn!("Towers can only be moved onto tower bases");
+                    continue;
+                };
+                let Ok((_, _, _, mut transform, built_on)) =
+                    towers.get_mut(*entity)
+                else {
+                    continue;
+                };
+                if let Some(built_on) = built_on {
+                    commands.spawn(tower_base_bundle(
+                        &assets,
+                        built_on.base.clone(),
+                        built_on.transform,
+                    ));
+                }
+                transform.translation = base_transform.translation();
+                commands.entity(*entity).insert(BuiltOn {
+                    base: base_kind.clone(),
+                    transform: base_transform.compute_transform(),
+                });
+                commands.entity(*base).despawn_recursive();
+                removed.push(*base);
+                particle_events.send(CreateParticleSystem {
+                    system: crate::graphics::ParticleSystemType::Landing,
+                    transform: Transform::from_translation(
+                        transform.translation,
+                    ),
+                });
+            }
+        }
+    }
+}
+
+fn count_kills(
+    mut ev_died: EventReader<EnemyDied>,
+    mut towers: Query<&mut Tower>,
+) {
+    for event in ev_died.iter() {
+        let Some(killer) = event.killer_tower else {
+            continue;
+        };
+        if let Ok(mut tower) = towers.get_mut(killer) {
+            tower.kills += 1;
+        }
+    }
+}
+
+/// Applies edited shooting intervals to towers that are already built when
+/// the catalog is hot reloaded. Everything else is looked up on every shot.
+fn tower_catalog_reload(
+    mut ev_asset: EventReader<AssetEvent<TowerCatalog>>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+    mut towers: Query<(&mut Tower, &TowerType)>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            let Some(catalog) = tower_catalogs.get(handle) else {
+                continue;
+            };
+            info!("Reloaded tower definitions");
+            for (mut tower, kind) in &mut towers {
+                if let Some(definition) = catalog.get(kind) {
+                    tower.update_shooting_timer(definition);
+                }
+            }
         }
     }
 }
@@ -1,112 +1,140 @@
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_mod_picking::Selection;
-use rand::distributions::WeightedIndex;
-use rand::prelude::*;
-use std::time::Duration;
+use seldom_fn_plugin::FnPluginExt;
 use strum::IntoEnumIterator;
 
 use crate::{
-    Tower, TowerBuildEvent, TowerSideEffects, TowerType, TowerUpgrades,
+    Bank, CurrentLevel, Enemy, GameAssets, LevelEvent, LevelLoadFailure,
+    LevelRegistry, LoadingProgress, MapLoadReport, PlayerBase, ReplayEvent,
+    ReplaySet, SaveEvent, TargetingMode, Tower, TowerBuildEvent, TowerCatalog,
+    TowerType, TowerUpgrades, UpgradeSideEffect, HEAL_PRICE,
 };
 
-fn min1(value: f32) -> f32 {
-    if value <= 0.0 {
-        1.
-    } else {
-        value
-    }
-}
-
 #[derive(Default, Resource)]
-struct UiState {
-    game_state: GameState,
-    wave_timer: Timer,
-    enemies_killed: i32,
-    money_in_bank: f32,
-    health: f32,
-    waves_finished: i32,
+pub(crate) struct UiState {
+    pub(crate) enemies_killed: i32,
+    pub(crate) waves_finished: i32,
     force_number: String,
 }
 
+/// Requests to change the [`GameState`] and notifications about changes.
 pub enum StateUpdateEvent {
     EnemyKilled(f32),
-    EnemyReachedPortal,
-    StartWave {
-        time_of_wave: f32,
-        spawn_interval: f32,
-    },
+    /// An enemy leaked into the base and dealt this much damage.
+    EnemyReachedPortal(f32),
+    StartWave,
+    /// Buys [`HEAL_AMOUNT`] health for [`HEAL_PRICE`].
+    Heal,
+    /// Sent by the wave director after it ended a wave.
+    EndWave,
+    /// Sent by the wave director after the last wave.
     GameWon,
     GameLost,
-    EndWave,
+    Pause,
+    Resume,
 }
 
-#[derive(Default)]
+/// The one place the phase of the game is stored in, systems that only make
+/// sense in some phases are gated with run conditions on it.
+#[derive(States, Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub enum GameState {
-    RunningWave,
     #[default]
-    TowerUpgrade,
-    GameWon,
-    GameLost,
+    Loading,
+    /// Level select, entering it despawns the level that was played.
+    MainMenu,
+    /// The map and waves of the started level are loading.
+    LoadingLevel,
+    /// Between waves, towers can be built and upgraded.
+    Building,
+    WaveRunning,
+    Paused,
+    Won,
+    Lost,
+}
+
+impl GameState {
+    /// Whether a level is loaded in this state.
+    pub fn in_level(&self) -> bool {
+        !matches!(
+            self,
+            GameState::Loading | GameState::MainMenu | GameState::LoadingLevel
+        )
+    }
+}
+
+/// Run condition for systems that need a loaded level.
+pub fn in_level(state: Res<State<GameState>>) -> bool {
+    state.0.in_level()
 }
 
 pub fn ui_plugin(app: &mut App) {
-    app.init_resource::<UiState>()
-        .add_event::<StateUpdateEvent>()
+    app.fn_plugin(game_state_plugin)
         .add_plugin(EguiPlugin)
         .add_startup_system(configure_ui)
-        .add_startup_system(configure_ui_state)
-        .add_system(main_game_screen)
-        .add_system(stat_window)
-        .add_system(state_update_handler);
+        .add_system(loading_screen.run_if(in_state(GameState::Loading)))
+        .add_system(level_select_screen.run_if(in_state(GameState::MainMenu)))
+        .add_system(
+            level_loading_screen.run_if(in_state(GameState::LoadingLevel)),
+        )
+        .add_system(main_game_screen.before(ReplaySet::Record).run_if(in_level))
+        .add_system(stat_window.run_if(in_level))
+        .add_system(map_report_window.run_if(in_level));
+}
+
+/// Game state bookkeeping without any egui screens, so it can also run in a
+/// headless app.
+pub fn game_state_plugin(app: &mut App) {
+    app.init_resource::<UiState>()
+        .add_event::<StateUpdateEvent>()
+        .add_system(configure_ui_state.in_schedule(OnExit(GameState::MainMenu)))
+        .add_system(state_update_handler.after(ReplaySet::Record));
 }
 
 #[derive(Default)]
 struct CurrentSelection {
     entity: Option<(Entity, GlobalTransform, Option<Tower>, Option<TowerType>)>,
+    /// Tower that is moved onto the next selected tower base.
+    relocating: Option<Entity>,
 }
 
 fn state_update_handler(
     mut ev_state_update: EventReader<StateUpdateEvent>,
     mut ui_state: ResMut<UiState>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
     for event in ev_state_update.iter() {
-        match event {
-            StateUpdateEvent::EnemyKilled(reward) => {
+        match (event, &state.0) {
+            (StateUpdateEvent::EnemyKilled(_), _) => {
                 ui_state.enemies_killed += 1;
-                ui_state.money_in_bank += reward;
             }
-            StateUpdateEvent::EnemyReachedPortal => {
-                ui_state.health -= 1.;
-                if ui_state.health < 0. {
-                    ui_state.game_state = GameState::GameLost;
-                }
+            (StateUpdateEvent::StartWave, GameState::Building) => {
+                next_state.set(GameState::WaveRunning);
             }
-            StateUpdateEvent::StartWave { time_of_wave, .. } => {
-                ui_state
-                    .wave_timer
-                    .set_duration(Duration::from_secs_f32(*time_of_wave));
-                ui_state.wave_timer.unpause();
-                ui_state.game_state = GameState::RunningWave;
+            (StateUpdateEvent::GameLost, _) => {
+                next_state.set(GameState::Lost);
             }
-            StateUpdateEvent::GameWon => {
-                ui_state.game_state = GameState::GameWon;
-                ui_state.wave_timer.pause();
+            (StateUpdateEvent::EndWave, _) => {
+                ui_state.waves_finished += 1;
             }
-            StateUpdateEvent::GameLost => {
-                ui_state.game_state = GameState::GameLost;
-                ui_state.wave_timer.pause();
+            (StateUpdateEvent::Pause, GameState::WaveRunning) => {
+                next_state.set(GameState::Paused);
             }
-            StateUpdateEvent::EndWave => {
-                ui_state.game_state = GameState::TowerUpgrade;
-                ui_state.wave_timer.pause();
-                ui_state.waves_finished += 1;
+            (StateUpdateEvent::Resume, GameState::Paused) => {
+                next_state.set(GameState::WaveRunning);
             }
+            _ => {}
         }
     }
 }
 
-fn stat_window(ui_state: Res<UiState>, mut egui_ctx: EguiContexts) {
+fn stat_window(
+    ui_state: Res<UiState>,
+    bank: Res<Bank>,
+    base: Res<PlayerBase>,
+    mut egui_ctx: EguiContexts,
+) {
     let ctx = egui_ctx.ctx_mut();
     egui::Window::new("Statistics")
         .interactable(false)
@@ -114,36 +142,84 @@ fn stat_window(ui_state: Res<UiState>, mut egui_ctx: EguiContexts) {
         .anchor(egui::Align2::RIGHT_TOP, [-5.0, 5.0])
         .show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label(format!(
-                    "Money in bank {:.2}",
-                    ui_state.money_in_bank
-                ));
+                ui.label(format!("Money in bank {:.2}", bank.balance()));
             });
             ui.horizontal(|ui| {
                 ui.label(format!("Enemies killed {}", ui_state.enemies_killed));
             });
             ui.horizontal(|ui| {
-                ui.label(format!("Health: {:.0}", ui_state.health));
+                ui.label(format!("Health: {:.0}", base.health()));
             });
         });
 }
 
-fn get_side_effect(
-    wave_multiplier: i32,
-    force: i32,
-) -> Option<TowerSideEffects> {
-    let mut rng = thread_rng();
-    let weights = WeightedIndex::new(TowerSideEffects::get_weights(
-        wave_multiplier,
-        force,
-    ))
-    .unwrap();
-    let options = [
-        None,
-        Some(TowerSideEffects::WeakShot(force as f32)),
-        Some(TowerSideEffects::HealShot(force as f32)),
-    ];
-    options[weights.sample(&mut rng)]
+fn loading_screen(progress: Res<LoadingProgress>, mut egui_ctx: EguiContexts) {
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        ui.heading("Loading");
+        ui.add(egui::ProgressBar::new(progress.fraction()).show_percentage());
+    });
+}
+
+fn level_select_screen(
+    mut egui_ctx: EguiContexts,
+    mut ev_level_writer: EventWriter<LevelEvent>,
+    mut ev_save_writer: EventWriter<SaveEvent>,
+    mut ev_replay_writer: EventWriter<ReplayEvent>,
+    assets: Res<GameAssets>,
+    registries: Res<Assets<LevelRegistry>>,
+    progress: Res<LoadingProgress>,
+    level_failure: Option<Res<LevelLoadFailure>>,
+) {
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        ui.heading("Select a level");
+        let registry = registries.get(&assets.levels);
+        for level in registry.iter().flat_map(|r| &r.levels) {
+            if ui.button(&level.name).clicked() {
+                ev_level_writer.send(LevelEvent::Start(level.name.clone()));
+            }
+        }
+        ui.separator();
+        if ui.button("Continue saved game").clicked() {
+            ev_save_writer.send(SaveEvent::Load);
+        }
+        if ui.button("Play replay").clicked() {
+            ev_replay_writer.send(ReplayEvent::Play);
+        }
+
+        if !progress.failed.is_empty() {
+            ui.separator();
+            ui.label("Failed to load:");
+            for path in &progress.failed {
+                ui.label(path);
+            }
+        }
+        if let Some(failure) = level_failure {
+            ui.separator();
+            ui.label(format!("Failed to load level {}:", failure.level));
+            for path in &failure.failed {
+                ui.label(path);
+            }
+        }
+    });
+}
+
+fn level_loading_screen(level: Res<CurrentLevel>, mut egui_ctx: EguiContexts) {
+    egui::CentralPanel::default().show(egui_ctx.ctx_mut(), |ui| {
+        ui.heading(format!("Loading {}", level.definition.name));
+    });
+}
+
+fn map_report_window(report: Res<MapLoadReport>, mut egui_ctx: EguiContexts) {
+    if report.is_ok() {
+        return;
+    }
+    egui::Window::new("Map errors")
+        .anchor(egui::Align2::LEFT_TOP, [5.0, 5.0])
+        .show(egui_ctx.ctx_mut(), |ui| {
+            for issue in &report.issues {
+                ui.label(issue.to_string());
+            }
+        });
 }
 
 fn main_game_screen(
@@ -155,19 +231,20 @@ fn main_game_screen(
         Option<&Tower>,
     )>,
     mut ui_state: ResMut<UiState>,
+    bank: Res<Bank>,
     mut egui_ctx: EguiContexts,
     mut ev_tower_build_writer: EventWriter<TowerBuildEvent>,
     mut ev_state_update_writer: EventWriter<StateUpdateEvent>,
+    mut ev_level_writer: EventWriter<LevelEvent>,
+    mut ev_save_writer: EventWriter<SaveEvent>,
+    mut ev_replay_writer: EventWriter<ReplayEvent>,
     mut current_selection: Local<CurrentSelection>,
-    time: Res<Time>,
+    assets: Res<GameAssets>,
+    tower_catalogs: Res<Assets<TowerCatalog>>,
+    enemies: Query<(), With<Enemy>>,
+    state: Res<State<GameState>>,
+    targeting_modes: Query<&TargetingMode>,
 ) {
-    if matches!(ui_state.game_state, GameState::RunningWave) {
-        ui_state.wave_timer.tick(time.delta());
-        if ui_state.wave_timer.just_finished() {
-            ui_state.game_state = GameState::TowerUpgrade;
-            ui_state.wave_timer.pause();
-        }
-    }
     let ctx = egui_ctx.ctx_mut();
     if !ctx.wants_pointer_input() {
         for (entity, selection, transform, tower_type, tower) in &selections {
@@ -186,27 +263,54 @@ fn main_game_screen(
         .max_height(300.0)
         .min_height(300.0)
         .show(ctx, |ui| {
-            ui.heading("Towering side effect");
-            if matches!(ui_state.game_state, GameState::RunningWave) {
-                    ui.label("Wave running");
+            ui.horizontal(|ui| {
+                ui.heading("Towering side effect");
+                if ui.button("Leave level").clicked() {
+                    ev_level_writer.send(LevelEvent::Leave);
+                }
+                if state.0 == GameState::Building
+                    && ui.button("Save").clicked()
+                {
+                    ev_save_writer.send(SaveEvent::Save);
+                }
+                if ui.button("Load").clicked() {
+                    ev_save_writer.send(SaveEvent::Load);
+                }
+                if ui.button("Save replay").clicked() {
+                    ev_replay_writer.send(ReplayEvent::Save);
+                }
+            });
+            if matches!(state.0, GameState::WaveRunning | GameState::Paused) {
+                ui.horizontal(|ui| {
                     ui.label(format!(
-                        "Remaining wave time: {:.2}",
-                        ui_state.wave_timer.remaining_secs()
+                        "Wave {} running",
+                        ui_state.waves_finished + 1
                     ));
-                }
+                    if state.0 == GameState::Paused {
+                        if ui.button("Resume").clicked() {
+                            ev_state_update_writer
+                                .send(StateUpdateEvent::Resume);
+                        }
+                    } else if ui.button("Pause").clicked() {
+                        ev_state_update_writer.send(StateUpdateEvent::Pause);
+                    }
+                });
+                ui.label(format!(
+                    "Remaining enemies: {}",
+                    enemies.iter().count()
+                ));
+            }
 
-            match ui_state.game_state {
-                GameState::TowerUpgrade | GameState::RunningWave => {
-                    if !matches!(ui_state.game_state, GameState::RunningWave) {
+            match state.0 {
+                GameState::Building
+                | GameState::WaveRunning
+                | GameState::Paused => {
+                    if state.0 == GameState::Building {
                         ui.horizontal(|ui| {
                             ui.allocate_ui(egui::Vec2::new(30.0, 30.0), |ui| {
                                 if ui.button("Run wave!").clicked() {
-                                    ev_state_update_writer.send(
-                                        StateUpdateEvent::StartWave {
-                                            time_of_wave: 40.0 * min1(ui_state.waves_finished as f32),
-                                            spawn_interval: 1.5,
-                                        },
-                                    );
+                                    ev_state_update_writer
+                                        .send(StateUpdateEvent::StartWave);
                                 }
                             });
                         });
@@ -214,10 +318,10 @@ fn main_game_screen(
 
                     ui.horizontal(|ui| {
                         ui.allocate_ui(egui::Vec2::new(30.0, 30.0), |ui| {
-                            if ui_state.money_in_bank >= 100.0 {
+                            if bank.can_afford(HEAL_PRICE) {
                                 if ui.button("Heal").clicked() {
-                                    ui_state.money_in_bank -= 100.0;
-                                    ui_state.health += 5.;
+                                    ev_state_update_writer
+                                        .send(StateUpdateEvent::Heal);
                                 }
                             } else {
                                 ui.label("Not enough money to heal");
@@ -236,6 +340,35 @@ fn main_game_screen(
                                     "Upgrade option for tower {:#?}",
                                     entity
                                 ));
+                                ui.label(format!("Kills: {}", tower.kills));
+                                ui.horizontal(|ui| {
+                                    if ui.button(format!("Sell for {:.2}", tower.refund())).clicked() {
+                                        ev_tower_build_writer.send(TowerBuildEvent::Sell { entity });
+                                        current_selection.entity = None;
+                                    }
+                                    if current_selection.relocating == Some(entity) {
+                                        ui.label("Select a tower base to move to");
+                                        if ui.button("Cancel move").clicked() {
+                                            current_selection.relocating = None;
+                                        }
+                                    } else if ui.button("Move").clicked() {
+                                        current_selection.relocating = Some(entity);
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Targeting");
+                                    let current = targeting_modes.get(entity).ok();
+                                    for mode in TargetingMode::iter() {
+                                        if ui
+                                            .selectable_label(current == Some(&mode), mode.to_string())
+                                            .clicked()
+                                        {
+                                            ev_tower_build_writer.send(
+                                                TowerBuildEvent::SetTargeting { entity, mode },
+                                            );
+                                        }
+                                    }
+                                });
                                 ui.horizontal_top(|ui| {
                                     if tower.upgrades.len() > 0 {
                                         ui.label(format!("Existing upgrades: {:?}", tower.upgrades));
@@ -249,7 +382,7 @@ fn main_game_screen(
                                             let price = upgrade_option
                                                 .get_price(ui_state.waves_finished, ui_state.force_number.parse().unwrap_or(1));
 
-                                            if ui_state.money_in_bank >= price {
+                                            if bank.can_afford(price) {
 
                                                 ui.allocate_ui(egui::Vec2::new(100.0, 30.0), |ui|{
                                                     if ui
@@ -260,12 +393,11 @@ fn main_game_screen(
                                                     {
                                                         let force: i32 = ui_state.force_number.parse().unwrap_or(1);
                                                         info!("Fired upgrade event");
-                                                        ui_state.money_in_bank -= price;
                                                         ev_tower_build_writer.send(
                                                             TowerBuildEvent::Upgrade {
                                                                 entity,
                                                                 effect: upgrade_option.set_force(force as f32),
-                                                                side_effect: get_side_effect(ui_state.waves_finished, force),
+                                                                side_effect: UpgradeSideEffect::Roll,
                                                             },
                                                         );
                                                         current_selection.entity = None;
@@ -283,27 +415,37 @@ fn main_game_screen(
                                     });
                                 });
                             } else {
+                                if let Some(tower) = current_selection.relocating {
+                                    if ui.button("Move tower here").clicked() {
+                                        ev_tower_build_writer.send(TowerBuildEvent::Relocate {
+                                            entity: tower,
+                                            base: entity,
+                                        });
+                                        current_selection.entity = None;
+                                        current_selection.relocating = None;
+                                    }
+                                }
                                 ui.label(format!(
                                     "Build options for {:#?}",
                                     entity
                                 ));
-                                for build_option in TowerType::iter() {
-                                    let price = build_option
+                                let catalog = tower_catalogs.get(&assets.towers);
+                                for definition in catalog.iter().flat_map(|c| &c.towers) {
+                                    let price = definition
                                         .get_price(ui_state.waves_finished);
 
-                                    if ui_state.money_in_bank >= price {
+                                    if bank.can_afford(price) {
                                         if ui
                                             .button(egui::RichText::new(
-                                                build_option.to_string(),
+                                                &definition.name,
                                             ))
                                             .clicked()
                                         {
                                             info!("Fired build event");
-                                            ui_state.money_in_bank -= price;
                                             ev_tower_build_writer.send(
                                                 TowerBuildEvent::Dispatch {
                                                     entity,
-                                                    kind: build_option,
+                                                    kind: definition.kind(),
                                                     pos: transform
                                                         .translation(),
                                                 },
@@ -313,7 +455,7 @@ fn main_game_screen(
                                     } else {
                                         ui.label(format!(
                                             "{}: Not enough money need {:.2}",
-                                            build_option.to_string(),
+                                            definition.name,
                                             price,
                                         ));
                                     }
@@ -323,12 +465,15 @@ fn main_game_screen(
                         }
                     });
                 }
-                GameState::GameWon => {
+                GameState::Won => {
                     ui.label("You won");
                 }
-                GameState::GameLost => {
+                GameState::Lost => {
                     ui.label("You Lost");
                 }
+                GameState::Loading
+                | GameState::MainMenu
+                | GameState::LoadingLevel => {}
             }
         });
 }
@@ -341,7 +486,5 @@ fn configure_ui(mut egui_ctx: EguiContexts) {
 }
 
 fn configure_ui_state(mut ui_state: ResMut<UiState>) {
-    ui_state.wave_timer = Timer::from_seconds(40.0, TimerMode::Once);
-    ui_state.money_in_bank = 1000.0;
-    ui_state.health = 5.;
+    *ui_state = UiState::default();
 }
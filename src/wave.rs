@@ -0,0 +1,283 @@
+use bevy::{asset::LoadState, prelude::*, reflect::TypeUuid};
+use serde::Deserialize;
+
+use crate::{
+    pathmanager::PathManager, player_base::damage_and_heal, spawn_enemy,
+    CurrentLevel, Enemy, EnemyArchetype, EnemyCatalog, EnemyType, GameAssets,
+    GameState, Health, PlayerBase, ReplaySet, RonAsset, RonAssetLoader,
+    StateUpdateEvent,
+};
+
+/// The waves of a level, loaded from a `*.waves.ron` file.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "5f0b7a3e-8d8c-4d0e-9a57-2c4f1e6b9d21"]
+pub struct WaveSchedule {
+    pub waves: Vec<WaveDefinition>,
+}
+
+impl RonAsset for WaveSchedule {
+    const EXTENSIONS: &'static [&'static str] = &["waves.ron"];
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WaveDefinition {
+    pub groups: Vec<SpawnGroup>,
+    #[serde(default)]
+    pub boss: Option<BossDefinition>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SpawnGroup {
+    /// Id of the enemy archetype to spawn.
+    pub enemy: String,
+    pub count: u32,
+    /// Seconds between two enemies of this group.
+    pub interval: f32,
+    /// Seconds after the wave start until the first enemy spawns.
+    #[serde(default)]
+    pub delay: f32,
+    /// Route the group walks, without one the enemies alternate between all
+    /// routes of the map.
+    #[serde(default)]
+    pub route: Option<i32>,
+}
+
+impl SpawnGroup {
+    /// Number of enemies of this group that are spawned after `elapsed`
+    /// seconds of the wave.
+    pub fn due(&self, elapsed: f32) -> u32 {
+        if elapsed < self.delay {
+            0
+        } else if self.interval <= 0.0 {
+            self.count
+        } else {
+            (((elapsed - self.delay) / self.interval) as u32 + 1)
+                .min(self.count)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BossDefinition {
+    /// Id of the enemy archetype the boss is based on.
+    pub enemy: String,
+    #[serde(default)]
+    pub delay: f32,
+    #[serde(default = "default_health_multiplier")]
+    pub health_multiplier: f32,
+    /// Route the boss walks, defaults to the first route of the map.
+    #[serde(default)]
+    pub route: Option<i32>,
+}
+
+fn default_health_multiplier() -> f32 {
+    1.0
+}
+
+#[derive(Reflect, Component)]
+pub struct Boss;
+
+#[derive(Resource)]
+pub(crate) struct WaveState {
+    /// The running or next wave, starting at 1.
+    pub(crate) wave_count: i32,
+    /// Seconds since the current wave started.
+    elapsed: f32,
+    /// Enemies spawned so far for each group of the current wave.
+    spawned: Vec<u32>,
+    boss_spawned: bool,
+}
+
+impl Default for WaveState {
+    fn default() -> Self {
+        Self {
+            wave_count: 1,
+            elapsed: 0.0,
+            spawned: vec![],
+            boss_spawned: false,
+        }
+    }
+}
+
+pub fn wave_plugin(app: &mut App) {
+    app.register_type::<Boss>()
+        .add_asset::<WaveSchedule>()
+        .init_asset_loader::<RonAssetLoader<WaveSchedule>>()
+        .insert_resource(WaveState::default())
+        .add_system(reset_waves.in_schedule(OnExit(GameState::MainMenu)))
+        .add_system(state_update_handler.after(ReplaySet::Record))
+        .add_system(
+            wave_director
+                .after(state_update_handler)
+                .after(damage_and_heal)
+                .run_if(in_state(GameState::WaveRunning)),
+        );
+}
+
+fn reset_waves(mut wave_state: ResMut<WaveState>) {
+    *wave_state = WaveState::default();
+}
+
+fn state_update_handler(
+    mut wave_state: ResMut<WaveState>,
+    mut ev_status_reader: EventReader<StateUpdateEvent>,
+    state: Res<State<GameState>>,
+) {
+    for event in ev_status_reader.iter() {
+        if let (StateUpdateEvent::StartWave, GameState::Building) =
+            (event, &state.0)
+        {
+            wave_state.elapsed = 0.0;
+            wave_state.spawned.clear();
+            wave_state.boss_spawned = false;
+        }
+    }
+}
+
+/// Spawns the groups and boss of the running wave from the [`WaveSchedule`]
+/// and ends the wave once everything is spawned and no enemy is left. After
+/// the last wave the game is won. Waits while the schedule is loading and
+/// leaves a destroyed base to [`PlayerBase`].
+fn wave_director(
+    mut commands: Commands,
+    mut wave_state: ResMut<WaveState>,
+    mut ev_state_update: EventWriter<StateUpdateEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    level: Option<Res<CurrentLevel>>,
+    schedules: Res<Assets<WaveSchedule>>,
+    enemy_catalogs: Res<Assets<EnemyCatalog>>,
+    paths: Query<(Entity, &PathManager)>,
+    enemies: Query<(), With<Enemy>>,
+    base: Res<PlayerBase>,
+    time: Res<Time>,
+) {
+    wave_state.elapsed += time.delta_seconds();
+
+    let Some(level) = level else {
+        return;
+    };
+    let Some(schedule) = schedules.get(&level.waves) else {
+        if asset_server.get_load_state(&level.waves) == LoadState::Failed {
+            error!(
+                "The waves of level {} failed to load",
+                level.definition.name
+            );
+            next_state.set(GameState::Building);
+        }
+        return;
+    };
+    let Some(wave) = schedule.waves.get(wave_state.wave_count as usize - 1)
+    else {
+        info!(
+            "No wave {} scheduled, the schedule ended",
+            wave_state.wave_count
+        );
+        next_state.set(GameState::Won);
+        ev_state_update.send(StateUpdateEvent::GameWon);
+        return;
+    };
+
+    // Spawning waits until the enemies and the path are available.
+    let catalog = enemy_catalogs.get(&assets.enemies);
+    let path = paths
+        .get_single()
+        .ok()
+        .filter(|(_, manager)| !manager.routes.is_empty());
+    let (Some(catalog), Some((path, manager))) = (catalog, path) else {
+        return;
+    };
+    let route_ids = manager.route_ids();
+
+    let wave_state = wave_state.as_mut();
+    wave_state.spawned.resize(wave.groups.len(), 0);
+    let mut spawned_now = false;
+    let mut spawning_done = true;
+
+    for (group, spawned) in wave.groups.iter().zip(&mut wave_state.spawned) {
+        let due = group.due(wave_state.elapsed);
+        while *spawned < due {
+            let kind = EnemyType(group.enemy.clone());
+            let route = group
+                .route
+                .unwrap_or(route_ids[*spawned as usize % route_ids.len()]);
+            match catalog.get(&kind) {
+                Some(archetype) => {
+                    spawned_now |= spawn_on_route(
+                        &mut commands,
+                        archetype,
+                        (path, manager),
+                        route,
+                    )
+                    .is_some();
+                }
+                None => warn!("Wave spawns unknown enemy {}", kind),
+            }
+            *spawned += 1;
+        }
+        spawning_done &= *spawned >= group.count;
+    }
+
+    if let Some(boss) = &wave.boss {
+        if !wave_state.boss_spawned && wave_state.elapsed >= boss.delay {
+            let kind = EnemyType(boss.enemy.clone());
+            let route = boss.route.unwrap_or(route_ids[0]);
+            let boss_entity = catalog.get(&kind).and_then(|archetype| {
+                spawn_on_route(&mut commands, archetype, (path, manager), route)
+                    .map(|entity| (entity, archetype))
+            });
+            match boss_entity {
+                Some((boss_entity, archetype)) => {
+                    commands.entity(boss_entity).insert((
+                        Name::new(format!("Boss {}", archetype.id)),
+                        Health {
+                            value: archetype.health * boss.health_multiplier,
+                        },
+                        Boss,
+                    ));
+                    spawned_now = true;
+                }
+                None => warn!("Wave failed to spawn boss {}", kind),
+            }
+            wave_state.boss_spawned = true;
+        }
+        spawning_done &= wave_state.boss_spawned;
+    }
+
+    // Enemies spawned this frame only show up in the query next frame.
+    if spawning_done
+        && !spawned_now
+        && enemies.is_empty()
+        && !base.is_destroyed()
+    {
+        info!("Wave {} finished", wave_state.wave_count);
+        ev_state_update.send(StateUpdateEvent::EndWave);
+        if wave_state.wave_count as usize >= schedule.waves.len() {
+            next_state.set(GameState::Won);
+            ev_state_update.send(StateUpdateEvent::GameWon);
+        } else {
+            next_state.set(GameState::Building);
+        }
+        wave_state.wave_count += 1;
+    }
+}
+
+fn spawn_on_route(
+    commands: &mut Commands,
+    archetype: &EnemyArchetype,
+    (path, manager): (Entity, &PathManager),
+    route: i32,
+) -> Option<Entity> {
+    let Some(start) = manager.route(route).and_then(|r| r.get_start()) else {
+        warn!("Wave uses unknown route {}", route);
+        return None;
+    };
+    Some(spawn_enemy(
+        commands,
+        archetype,
+        path,
+        route,
+        start.location,
+    ))
+}
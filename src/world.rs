@@ -1,12 +1,18 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
 
 use crate::{
     graphics::CreateParticleSystem,
+    in_level,
+    init::asset_path,
     pathmanager::{PathManager, PathManagerUpdate},
-    GameAssets,
+    CurrentLevel, GameAssets, PhysicsBundle,
 };
 use bevy::{
-    gltf::{Gltf, GltfNode},
+    asset::LoadState,
+    gltf::{Gltf, GltfMesh, GltfNode},
     pbr::NotShadowCaster,
     prelude::*,
 };
@@ -14,7 +20,7 @@ use bevy_mod_picking::*;
 use bevy_rapier3d::prelude::*;
 use std::fmt::Display;
 
-#[derive(Reflect, Component)]
+#[derive(Reflect, Component, Clone)]
 pub enum TowerBase {
     Bad(String),
     Normal(String),
@@ -95,16 +101,27 @@ impl FromStr for Proxy {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         //Proxy,kind,route_id,node_id,movement_type
         let parts = s.split(",").collect::<Vec<&str>>();
+        let part = |i: usize| {
+            parts.get(i).copied().ok_or_else(|| {
+                anyhow::anyhow!("proxy {} has only {} parts", s, parts.len())
+            })
+        };
+        // Blender appends .001 and the like to duplicated object names
+        let movement_type = part(4)?.split('.').next().unwrap_or_default();
         Ok(Self {
-            route_id: parts[2].parse()?,
-            node_id: parts[3].parse()?,
-            kind: match parts[1] {
+            route_id: part(2)?.parse()?,
+            node_id: part(3)?.parse()?,
+            kind: match part(1)? {
                 "route" => ProxyKind::Route,
-                _ => ProxyKind::Route,
+                "portal" => ProxyKind::Portal,
+                x => return Err(anyhow::anyhow!("unknown proxy kind {}", x)),
             },
-            movement_type: match parts[4] {
-                "walk" => MovementType::Walking,
-                _ => MovementType::Falling,
+            movement_type: match movement_type {
+                "walk" | "walking" => MovementType::Walking,
+                "fall" | "falling" => MovementType::Falling,
+                x => {
+                    return Err(anyhow::anyhow!("unknown movement type {}", x))
+                }
             },
             location: Vec3::ZERO,
         })
@@ -117,146 +134,415 @@ impl Display for Proxy {
     }
 }
 
+/// Problems found in the node names of a map. Nodes with a problem are left
+/// out of the map instead of crashing the game.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapIssue {
+    BadTowerBase {
+        node: String,
+        reason: String,
+    },
+    BadProxy {
+        node: String,
+        reason: String,
+    },
+    DuplicateNode {
+        node: String,
+        route_id: i32,
+        node_id: i32,
+    },
+    NodeGap {
+        route_id: i32,
+        after: i32,
+        next: i32,
+    },
+    MissingNode {
+        node: String,
+    },
+    BadCollider {
+        node: String,
+    },
+    MissingPortal,
+    MissingScene,
+    /// The collision scene failed to load, the terrain has no colliders.
+    MissingCollision {
+        path: String,
+    },
+}
+
+impl Display for MapIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapIssue::BadTowerBase { node, reason } => {
+                write!(f, "Tower base {}: {}", node, reason)
+            }
+            MapIssue::BadProxy { node, reason } => {
+                write!(f, "Proxy {}: {}", node, reason)
+            }
+            MapIssue::DuplicateNode {
+                node,
+                route_id,
+                node_id,
+            } => write!(
+                f,
+                "Proxy {}: node {} of route {} exists already",
+                node, node_id, route_id
+            ),
+            MapIssue::NodeGap {
+                route_id,
+                after,
+                next,
+            } => write!(
+                f,
+                "Route {} continues with node {} after node {}",
+                route_id, next, after
+            ),
+            MapIssue::MissingNode { node } => {
+                write!(f, "Node {} has no data", node)
+            }
+            MapIssue::BadCollider { node } => {
+                write!(f, "Node {} has no mesh to build a collider from", node)
+            }
+            MapIssue::MissingPortal => write!(f, "Map has no portal"),
+            MapIssue::MissingScene => write!(f, "Map has no default scene"),
+            MapIssue::MissingCollision { path } => {
+                write!(f, "Collision scene {} failed to load", path)
+            }
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MapLoadReport {
+    pub issues: Vec<MapIssue>,
+}
+
+impl MapLoadReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether the node with this name is left out of the map.
+    pub fn rejects(&self, name: &str) -> bool {
+        self.issues.iter().any(|issue| match issue {
+            MapIssue::BadTowerBase { node, .. }
+            | MapIssue::BadProxy { node, .. }
+            | MapIssue::DuplicateNode { node, .. }
+            | MapIssue::MissingNode { node } => node == name,
+            _ => false,
+        })
+    }
+}
+
+/// Checks the node names of a map. Of nodes with the same route and node id
+/// the first by name is kept.
+pub fn validate_map<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> MapLoadReport {
+    let mut names = names.into_iter().collect::<Vec<_>>();
+    names.sort();
+
+    let mut issues = vec![];
+    let mut routes = BTreeMap::<i32, BTreeSet<i32>>::new();
+    let mut has_portal = false;
+    for name in names {
+        let lowercase = name.to_lowercase();
+        if lowercase.starts_with("tower") {
+            if let Err(e) = TowerBase::from_str(name) {
+                issues.push(MapIssue::BadTowerBase {
+                    node: name.to_owned(),
+                    reason: e.to_string(),
+                });
+            }
+        } else if lowercase.starts_with("proxy") {
+            match Proxy::from_str(name) {
+                Ok(proxy) => {
+                    let nodes = routes.entry(proxy.route_id).or_default();
+                    if !nodes.insert(proxy.node_id) {
+                        issues.push(MapIssue::DuplicateNode {
+                            node: name.to_owned(),
+                            route_id: proxy.route_id,
+                            node_id: proxy.node_id,
+                        });
+                    }
+                }
+                Err(e) => issues.push(MapIssue::BadProxy {
+                    node: name.to_owned(),
+                    reason: e.to_string(),
+                }),
+            }
+        } else if lowercase.starts_with("portal") {
+            has_portal = true;
+        }
+    }
+
+    for (route_id, nodes) in routes {
+        for (after, next) in nodes.iter().zip(nodes.iter().skip(1)) {
+            if next - after > 1 {
+                issues.push(MapIssue::NodeGap {
+                    route_id,
+                    after: *after,
+                    next: *next,
+                });
+            }
+        }
+    }
+    if !has_portal {
+        issues.push(MapIssue::MissingPortal);
+    }
+
+    MapLoadReport { issues }
+}
+
 pub fn world_plugin(app: &mut App) {
     app.register_type::<Proxy>()
         .register_type::<TowerBase>()
         .register_type::<Route>()
+        .init_resource::<MapLoadReport>()
         .add_startup_system(spawn_basic_scene)
-        .add_system(handle_map_spawn);
+        .add_system(spawn_map.run_if(in_level));
 }
 
-fn handle_map_spawn(
+/// Spawns the map of the current level once its GLTF and collision scene are
+/// loaded. A collision scene that failed to load is reported and left out.
+fn spawn_map(
     mut ev_particles_writer: EventWriter<CreateParticleSystem>,
-    mut ev_asset: EventReader<AssetEvent<Gltf>>,
     mut ev_pathmanager_update: EventWriter<PathManagerUpdate>,
     mut commands: Commands,
     assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    level: Option<Res<CurrentLevel>>,
+    maps: Query<(), With<PathManager>>,
     assets_gltf: Res<Assets<Gltf>>,
     nodes: Res<Assets<GltfNode>>,
+    meshes: (Res<Assets<GltfMesh>>, Res<Assets<Mesh>>),
 ) {
-    for ev in ev_asset.iter() {
-        match ev {
-            AssetEvent::Created { handle } => {
-                if handle == assets.map() {
-                    let map = assets_gltf.get(handle).unwrap();
-
-                    commands
-                        .spawn((
-                            SceneBundle {
-                                scene: map.default_scene.clone().unwrap(),
-                                ..Default::default()
-                            },
-                            Name::new("Map"),
-                            PathManager::new(),
-                        ))
-                        .with_children(|commands| {
-                            for (name, node_handle) in map.named_nodes.iter() {
-                                if name.to_lowercase().starts_with("tower") {
-                                    let tower_base =
-                                        TowerBase::from_str(name).unwrap();
-                                    let node = nodes.get(node_handle).unwrap();
-                                    commands.spawn((
-                                        PbrBundle {
-                                            mesh: assets
-                                                .get_capsule_shape()
-                                                .clone(),
-                                            material: assets
-                                                .default_collider_color
-                                                .clone(),
-                                            transform: node
-                                                .transform
-                                                .mul_transform(
-                                                    Transform::from_xyz(
-                                                        0.0, 1.0, 0.0,
-                                                    ),
-                                                )
-                                                .with_scale(Vec3::new(
-                                                    1.5, 1.5, 1.5,
-                                                )),
-                                            ..Default::default()
-                                        },
-                                        Name::new(format!(
-                                            "Tower_Base_{}",
-                                            tower_base
-                                        )),
-                                        Highlighting {
-                                            initial: assets
-                                                .default_collider_color
-                                                .clone(),
-                                            hovered: Some(
-                                                assets
-                                                    .tower_base_selected_color
-                                                    .clone(),
-                                            ),
-                                            pressed: Some(
-                                                assets
-                                                    .tower_base_selected_color
-                                                    .clone(),
-                                            ),
-                                            selected: Some(
-                                                assets
-                                                    .tower_base_selected_color
-                                                    .clone(),
-                                            ),
-                                        },
-                                        NotShadowCaster,
-                                        PickableBundle::default(),
-                                        tower_base,
-                                    ));
-                                } else if name
-                                    .to_lowercase()
-                                    .starts_with("proxy")
-                                {
-                                    let mut proxy =
-                                        Proxy::from_str(name).unwrap();
-
-                                    let node = nodes.get(node_handle).unwrap();
-                                    proxy.location = node.transform.translation;
-
-                                    if matches!(proxy.kind, ProxyKind::Route) {
-                                        commands.spawn((
-                                            SpatialBundle {
-                                                transform: node.transform,
-                                                ..Default::default()
-                                            },
-                                            Name::new(format!(
-                                                "Proxy_{}",
-                                                proxy
-                                            )),
-                                            proxy.clone(),
-                                        ));
-
-                                        ev_pathmanager_update.send(
-                                            PathManagerUpdate::AddNode(proxy),
-                                        );
-                                    }
-                                } else if name
-                                    .to_lowercase()
-                                    .starts_with("portal")
-                                {
-                                    let node = nodes.get(node_handle).unwrap();
-                                    commands.spawn((
-                                        SpatialBundle {
-                                            transform: node.transform.clone(),
-                                            ..Default::default()
-                                        },
-                                        Name::new("Portal"),
-                                    ));
-
-                                    ev_particles_writer.send(CreateParticleSystem{
-                                        system: crate::graphics::ParticleSystemType::Portal,
-                                        transform: node.transform,
-                                    });
-                                }
-                            }
-                        });
+    if !maps.is_empty() {
+        return;
+    }
+    let Some(level) = level else {
+        return;
+    };
+    let Some(map) = assets_gltf.get(&level.map) else {
+        return;
+    };
+    let mut collision_issue = None;
+    let collision = match &level.collision {
+        Some(handle) => match assets_gltf.get(handle) {
+            Some(collision) => Some(collision),
+            None if asset_server.get_load_state(handle)
+                == LoadState::Failed =>
+            {
+                collision_issue = Some(MapIssue::MissingCollision {
+                    path: asset_path(&asset_server, &handle.clone_untyped()),
+                });
+                None
+            }
+            None => return,
+        },
+        None => None,
+    };
+    let mut report = validate_map(map.named_nodes.keys().map(String::as_str));
+    if map.default_scene.is_none() {
+        report.issues.push(MapIssue::MissingScene);
+    }
+    report.issues.extend(collision_issue);
+
+    commands
+        .spawn((
+            SceneBundle {
+                scene: map.default_scene.clone().unwrap_or_default(),
+                ..Default::default()
+            },
+            Name::new("Map"),
+            PathManager::new(),
+        ))
+        .with_children(|commands| {
+            for (name, node_handle) in &map.named_nodes {
+                if report.rejects(name) {
+                    continue;
+                }
+                let Some(node) = nodes.get(node_handle) else {
+                    report
+                        .issues
+                        .push(MapIssue::MissingNode { node: name.clone() });
+                    continue;
+                };
+                spawn_map_node(
+                    commands,
+                    name,
+                    node,
+                    &assets,
+                    &mut ev_particles_writer,
+                    &mut ev_pathmanager_update,
+                );
+                if let Some(shape) = collider_shape(name, None) {
+                    spawn_collider(commands, name, node, shape, &meshes)
+                        .unwrap_or_else(|issue| report.issues.push(issue));
                 }
             }
-            x => info!("Asset Event {:?} not handled", x),
+
+            for (name, node_handle) in
+                collision.iter().flat_map(|c| &c.named_nodes)
+            {
+                let Some(node) = nodes.get(node_handle) else {
+                    report
+                        .issues
+                        .push(MapIssue::MissingNode { node: name.clone() });
+                    continue;
+                };
+                if node.mesh.is_none() {
+                    continue;
+                }
+                let shape =
+                    collider_shape(name, Some(ComputedColliderShape::TriMesh));
+                if let Some(shape) = shape {
+                    spawn_collider(commands, name, node, shape, &meshes)
+                        .unwrap_or_else(|issue| report.issues.push(issue));
+                }
+            }
+        });
+
+    for issue in &report.issues {
+        warn!("{}", issue);
+    }
+    commands.insert_resource(report);
+}
+
+/// Collider shape for a node named `*_col` (trimesh) or `*_convex` (convex
+/// hull), otherwise `default`.
+fn collider_shape(
+    name: &str,
+    default: Option<ComputedColliderShape>,
+) -> Option<ComputedColliderShape> {
+    // Blender appends .001 and the like to duplicated object names
+    let name = name.split('.').next().unwrap_or_default().to_lowercase();
+    if name.ends_with("_convex") {
+        Some(ComputedColliderShape::ConvexHull)
+    } else if name.ends_with("_col") {
+        Some(ComputedColliderShape::TriMesh)
+    } else {
+        default
+    }
+}
+
+/// Spawns fixed colliders for all primitives of the mesh of `node`.
+fn spawn_collider(
+    commands: &mut ChildBuilder,
+    name: &str,
+    node: &GltfNode,
+    shape: ComputedColliderShape,
+    (gltf_meshes, meshes): &(Res<Assets<GltfMesh>>, Res<Assets<Mesh>>),
+) -> Result<(), MapIssue> {
+    let bad_collider = || MapIssue::BadCollider {
+        node: name.to_owned(),
+    };
+    let gltf_mesh = node
+        .mesh
+        .as_ref()
+        .and_then(|mesh| gltf_meshes.get(mesh))
+        .ok_or_else(bad_collider)?;
+    for primitive in &gltf_mesh.primitives {
+        let bundle = meshes
+            .get(&primitive.mesh)
+            .and_then(|mesh| PhysicsBundle::from_mesh(mesh, &shape))
+            .ok_or_else(bad_collider)?;
+        commands.spawn((
+            TransformBundle::from_transform(node.transform),
+            Name::new(format!("Collider_{}", name)),
+            bundle.make_fixed(),
+        ));
+    }
+    Ok(())
+}
+
+/// Spawns the tower base, proxy or portal for a named node that passed
+/// [`validate_map`].
+fn spawn_map_node(
+    commands: &mut ChildBuilder,
+    name: &str,
+    node: &GltfNode,
+    assets: &GameAssets,
+    ev_particles_writer: &mut EventWriter<CreateParticleSystem>,
+    ev_pathmanager_update: &mut EventWriter<PathManagerUpdate>,
+) {
+    let lowercase = name.to_lowercase();
+    if lowercase.starts_with("tower") {
+        let Ok(tower_base) = TowerBase::from_str(name) else {
+            return;
+        };
+        commands.spawn(tower_base_bundle(
+            assets,
+            tower_base,
+            node.transform
+                .mul_transform(Transform::from_xyz(0.0, 1.0, 0.0))
+                .with_scale(Vec3::new(1.5, 1.5, 1.5)),
+        ));
+    } else if lowercase.starts_with("proxy") {
+        let Ok(mut proxy) = Proxy::from_str(name) else {
+            return;
+        };
+        proxy.location = node.transform.translation;
+
+        commands.spawn((
+            SpatialBundle {
+                transform: node.transform,
+                ..Default::default()
+            },
+            Name::new(format!("Proxy_{}", proxy)),
+            proxy.clone(),
+        ));
+
+        if matches!(proxy.kind, ProxyKind::Portal) {
+            ev_particles_writer.send(CreateParticleSystem {
+                system: crate::graphics::ParticleSystemType::Portal,
+                transform: node.transform,
+            });
         }
+
+        ev_pathmanager_update.send(PathManagerUpdate::AddNode(proxy));
+    } else if lowercase.starts_with("portal") {
+        commands.spawn((
+            SpatialBundle {
+                transform: node.transform,
+                ..Default::default()
+            },
+            Name::new("Portal"),
+        ));
+
+        ev_particles_writer.send(CreateParticleSystem {
+            system: crate::graphics::ParticleSystemType::Portal,
+            transform: node.transform,
+        });
     }
 }
 
+/// A selectable tower base towers can be built on.
+pub(crate) fn tower_base_bundle(
+    assets: &GameAssets,
+    tower_base: TowerBase,
+    transform: Transform,
+) -> impl Bundle {
+    (
+        PbrBundle {
+            mesh: assets.get_capsule_shape().clone(),
+            material: assets.default_collider_color.clone(),
+            transform,
+            ..Default::default()
+        },
+        Name::new(format!("Tower_Base_{}", tower_base)),
+        Highlighting {
+            initial: assets.default_collider_color.clone(),
+            hovered: Some(assets.tower_base_selected_color.clone()),
+            pressed: Some(assets.tower_base_selected_color.clone()),
+            selected: Some(assets.tower_base_selected_color.clone()),
+        },
+        NotShadowCaster,
+        PickableBundle::default(),
+        tower_base,
+    )
+}
+
 fn spawn_basic_scene(
     mut commands: Commands,
     mut rapier_config: ResMut<RapierConfiguration>,
@@ -0,0 +1,93 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn bank(app: &App) -> &Bank {
+    app.world.resource::<Bank>()
+}
+
+fn sources(app: &App) -> Vec<TransactionSource> {
+    bank(app).ledger().iter().map(|t| t.source).collect()
+}
+
+#[test]
+fn negative_and_nan_prices_are_refused() {
+    let mut bank = Bank::new(100.0, 1);
+    assert!(!bank.try_spend(TransactionSource::Upgrade, -50.0));
+    assert!(!bank.try_spend(TransactionSource::Upgrade, f32::NAN));
+    assert!(!bank.try_spend(TransactionSource::Upgrade, f32::INFINITY));
+    assert_eq!(bank.balance(), 100.0);
+    assert!(bank.ledger().is_empty());
+}
+
+#[test]
+fn spending_is_refused_without_enough_money() {
+    let mut bank = Bank::new(100.0, 1);
+    assert!(!bank.try_spend(TransactionSource::Build, 150.0));
+    assert!(bank.try_spend(TransactionSource::Heal, 60.0));
+    bank.earn(TransactionSource::Bounty, 10.0);
+    assert_eq!(bank.balance(), 50.0);
+    assert_eq!(
+        bank.ledger(),
+        &[
+            Transaction {
+                source: TransactionSource::Heal,
+                amount: -60.0,
+                wave: 1,
+            },
+            Transaction {
+                source: TransactionSource::Bounty,
+                amount: 10.0,
+                wave: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn builds_are_charged_and_sales_refunded() {
+    let mut app = loaded_app();
+    let start = bank(&app).balance();
+    let gun = build_tower(&mut app, "gun", Vec3::ZERO);
+    let tower = app.world.get::<Tower>(gun).unwrap();
+    let (spent, refund) = (tower.spent, tower.refund());
+    assert_eq!(bank(&app).balance(), start - spent);
+
+    app.world.send_event(TowerBuildEvent::Sell { entity: gun });
+    app.update();
+    assert_eq!(bank(&app).balance(), start - spent + refund);
+    assert_eq!(
+        sources(&app),
+        vec![TransactionSource::Build, TransactionSource::Sell]
+    );
+}
+
+#[test]
+fn towers_that_cannot_be_paid_are_not_built() {
+    let mut app = loaded_app();
+    app.insert_resource(Bank::new(10.0, 1));
+    let base = app.world.spawn_empty().id();
+    assert!(build_tower_on(&mut app, base, "gun", Vec3::ZERO).is_none());
+    assert_eq!(bank(&app).balance(), 10.0);
+}
+
+#[test]
+fn waves_pay_income_and_interest() {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(0, 0.0), drones(0, 0.0)]);
+    spawn_straight_path(&mut app);
+    app.insert_resource(Bank::new(1000.0, 1));
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 0.5);
+    assert_eq!(game_state(&app), GameState::Building);
+
+    let interest = (1000.0 + WAVE_INCOME) * INTEREST_RATE;
+    assert_eq!(bank(&app).balance(), 1000.0 + WAVE_INCOME + interest);
+    assert_eq!(
+        sources(&app),
+        vec![TransactionSource::WaveIncome, TransactionSource::Interest]
+    );
+    assert!(bank(&app).ledger().iter().all(|t| t.wave == 1));
+}
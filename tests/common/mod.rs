@@ -0,0 +1,153 @@
+#![allow(dead_code)]
+
+use bevy::{asset::LoadState, prelude::*};
+use towerish_side_effects::*;
+
+/// Updates the app until the asset behind `handle` finished loading.
+pub fn load<T: bevy::asset::Asset>(app: &mut App, handle: &Handle<T>) {
+    for _ in 0..1000 {
+        app.update();
+        match app.world.resource::<AssetServer>().get_load_state(handle) {
+            LoadState::Loaded => return,
+            LoadState::Failed => panic!("failed to load {:?}", handle),
+            _ => std::thread::sleep(std::time::Duration::from_millis(1)),
+        }
+    }
+    panic!("timed out loading {:?}", handle);
+}
+
+pub const LEVEL: &str = "Map A";
+
+/// A headless app in the main menu with all game definitions loaded.
+pub fn menu_app() -> App {
+    let mut app = headless_app();
+    app.update();
+    let assets = app.world.resource::<GameAssets>();
+    let (towers, enemies, levels) = (
+        assets.towers.clone(),
+        assets.enemies.clone(),
+        assets.levels.clone(),
+    );
+    load(&mut app, &towers);
+    load(&mut app, &enemies);
+    load(&mut app, &levels);
+    while game_state(&app) == GameState::Loading {
+        app.update();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    app
+}
+
+/// A headless app with all game definitions loaded and [`LEVEL`] started.
+pub fn loaded_app() -> App {
+    let mut app = menu_app();
+    app.world.send_event(LevelEvent::Start(LEVEL.to_string()));
+    enter_level(&mut app);
+    app
+}
+
+/// Updates the app until the started level finished loading.
+pub fn enter_level(app: &mut App) {
+    for _ in 0..1000 {
+        app.update();
+        if game_state(app) == GameState::Building {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("level did not load, state is {:?}", game_state(app));
+}
+
+/// Replaces the loaded wave schedule.
+pub fn set_waves(app: &mut App, waves: Vec<WaveDefinition>) {
+    let handle = app.world.resource::<CurrentLevel>().waves.clone();
+    app.world
+        .resource_mut::<Assets<WaveSchedule>>()
+        .set_untracked(handle, WaveSchedule { waves });
+}
+
+pub fn drones(count: u32, interval: f32) -> WaveDefinition {
+    WaveDefinition {
+        groups: vec![SpawnGroup {
+            enemy: "drone".to_string(),
+            count,
+            interval,
+            delay: 0.0,
+            route: None,
+        }],
+        boss: None,
+    }
+}
+
+/// Builds a tower of `kind` at `pos` on an empty entity standing in for a
+/// tower base.
+pub fn build_tower(app: &mut App, kind: &str, pos: Vec3) -> Entity {
+    let base = app.world.spawn_empty().id();
+    build_tower_on(app, base, kind, pos).expect("tower was built")
+}
+
+/// Builds a tower of `kind` at `pos` on `base`, `None` if it was not built.
+pub fn build_tower_on(
+    app: &mut App,
+    base: Entity,
+    kind: &str,
+    pos: Vec3,
+) -> Option<Entity> {
+    app.world.send_event(TowerBuildEvent::Dispatch {
+        entity: base,
+        kind: TowerType(kind.to_string()),
+        pos,
+    });
+    app.update();
+    app.world
+        .query_filtered::<(Entity, &Transform), With<Tower>>()
+        .iter(&app.world)
+        .find(|(_, transform)| transform.translation == pos)
+        .map(|(entity, _)| entity)
+}
+
+pub fn game_state(app: &App) -> GameState {
+    app.world.resource::<State<GameState>>().0.clone()
+}
+
+pub fn run_for(app: &mut App, seconds: f32) {
+    for _ in 0..(seconds / HEADLESS_TIMESTEP).round() as usize {
+        app.update();
+    }
+}
+
+/// A level with a single drone that just started walking a straight route.
+pub fn spawn_single_drone() -> (App, Entity) {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(1, 0.0)]);
+    spawn_straight_path(&mut app);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 0.1);
+    let drone = app
+        .world
+        .query_filtered::<Entity, With<Enemy>>()
+        .single(&app.world);
+    (app, drone)
+}
+
+pub fn spawn_straight_path(app: &mut App) -> Entity {
+    spawn_routes(app, &[0])
+}
+
+/// Spawns a map with a straight route along x for every `route_id`, each
+/// offset by 10 on the z axis.
+pub fn spawn_routes(app: &mut App, route_ids: &[i32]) -> Entity {
+    let mut path = PathManager::new();
+    for (i, route_id) in route_ids.iter().enumerate() {
+        for node_id in 0..3 {
+            path.push(Proxy {
+                route_id: *route_id,
+                node_id,
+                kind: ProxyKind::Route,
+                movement_type: MovementType::Walking,
+                location: Vec3::new(node_id as f32 * 5.0, 0.0, i as f32 * 10.0),
+            });
+        }
+    }
+    app.world.spawn(path).id()
+}
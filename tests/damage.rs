@@ -0,0 +1,75 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+#[test]
+fn piercing_damage_goes_through_armor() {
+    let resistances = Resistances::default();
+    assert_eq!(
+        DamageType::Kinetic.damage(10.0, Armor(0.5), &resistances),
+        0.5
+    );
+    assert_eq!(
+        DamageType::Piercing.damage(10.0, Armor(0.5), &resistances),
+        1.0
+    );
+    assert_eq!(
+        DamageType::Kinetic.damage(1.0, Armor(0.5), &resistances),
+        0.0
+    );
+}
+
+#[test]
+fn resistances_scale_the_damage_of_their_type() {
+    let resistances = Resistances {
+        explosive: 0.5,
+        piercing: -0.5,
+        ..Default::default()
+    };
+    assert_eq!(
+        DamageType::Explosive.damage(10.0, Armor(0.0), &resistances),
+        0.5
+    );
+    assert_eq!(
+        DamageType::Piercing.damage(10.0, Armor(0.0), &resistances),
+        1.5
+    );
+    assert_eq!(
+        DamageType::Kinetic.damage(10.0, Armor(0.0), &resistances),
+        1.0
+    );
+}
+
+/// Damage per second of the shipped tower `tower` against `enemy`.
+fn dps(app: &App, tower: &str, enemy: &str) -> f32 {
+    let assets = app.world.resource::<GameAssets>();
+    let towers = app.world.resource::<Assets<TowerCatalog>>();
+    let enemies = app.world.resource::<Assets<EnemyCatalog>>();
+    let tower = towers
+        .get(&assets.towers)
+        .unwrap()
+        .get(&TowerType(tower.to_string()))
+        .unwrap();
+    let enemy = enemies
+        .get(&assets.enemies)
+        .unwrap()
+        .get(&EnemyType(enemy.to_string()))
+        .unwrap();
+    tower.damage_type.damage(
+        tower.projectile.force,
+        enemy.armor,
+        &enemy.resistances,
+    ) / tower.shooting_interval
+}
+
+#[test]
+fn rockets_counter_barges_and_snipers_counter_drones() {
+    let app = loaded_app();
+    for tower in ["gun", "sniper"] {
+        assert!(dps(&app, "rocket", "barge") > dps(&app, tower, "barge"));
+    }
+    assert!(dps(&app, "sniper", "drone") > dps(&app, "rocket", "drone"));
+    assert!(dps(&app, "sniper", "drone") > dps(&app, "sniper", "barge"));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use common::*;
+use towerish_side_effects::*;
+
+fn drone_and_gun() -> (App, Entity, Entity) {
+    let (mut app, drone) = spawn_single_drone();
+    let gun = {
+        let assets = app.world.resource::<GameAssets>();
+        let catalogs = app.world.resource::<Assets<TowerCatalog>>();
+        let definition = catalogs
+            .get(&assets.towers)
+            .unwrap()
+            .get(&TowerType("gun".to_string()))
+            .unwrap();
+        Tower::new(definition)
+    };
+    let gun = app.world.spawn(gun).id();
+    (app, drone, gun)
+}
+
+fn hit(app: &mut App, drone: Entity, tower: Option<Entity>, force: f32) {
+    app.world.send_event(HitEvent {
+        entity: drone,
+        tower,
+        force,
+        damage_type: DamageType::Kinetic,
+        status_effects: vec![],
+        side_effects: vec![],
+    });
+}
+
+#[test]
+fn hits_in_one_frame_kill_an_enemy_once() {
+    let (mut app, drone, gun) = drone_and_gun();
+    let mut deaths = ManualEventReader::<EnemyDied>::default();
+    // A drone has 2 health and every hit deals 1.5 damage
+    for _ in 0..3 {
+        hit(&mut app, drone, Some(gun), 15.0);
+    }
+    app.update();
+
+    let died: Vec<_> = deaths
+        .iter(app.world.resource::<Events<EnemyDied>>())
+        .collect();
+    assert_eq!(died.len(), 1);
+    assert_eq!(died[0].entity, drone);
+    assert_eq!(died[0].kind, EnemyType("drone".to_string()));
+    assert_eq!(died[0].killer_tower, Some(gun));
+    assert!((died[0].overkill - 1.0).abs() < 1e-4);
+    assert!(app.world.get_entity(drone).is_none());
+
+    app.update();
+    assert_eq!(app.world.get::<Tower>(gun).unwrap().kills, 1);
+    let bounties = app
+        .world
+        .resource::<Bank>()
+        .ledger()
+        .iter()
+        .filter(|transaction| transaction.source == TransactionSource::Bounty)
+        .count();
+    assert_eq!(bounties, 1);
+}
+
+#[test]
+fn only_the_final_blow_counts_as_a_kill() {
+    let (mut app, drone, gun) = drone_and_gun();
+    hit(&mut app, drone, Some(gun), 15.0);
+    app.update();
+    assert!(app.world.get_entity(drone).is_some());
+    hit(&mut app, drone, None, 15.0);
+    run_for(&mut app, 0.1);
+    assert!(app.world.get_entity(drone).is_none());
+    assert_eq!(app.world.get::<Tower>(gun).unwrap().kills, 0);
+}
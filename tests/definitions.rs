@@ -0,0 +1,68 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+#[test]
+fn shipped_tower_catalog_loads() {
+    let app = loaded_app();
+    let assets = app.world.resource::<GameAssets>();
+
+    let catalogs = app.world.resource::<Assets<TowerCatalog>>();
+    let catalog = catalogs.get(&assets.towers).unwrap();
+    let gun = catalog.get(&TowerType("gun".to_string())).unwrap();
+    assert_eq!(gun.get_price(2), 1000.0);
+    assert!(!gun.projectile.homing);
+    assert!(catalog
+        .towers
+        .iter()
+        .all(|tower| tower.shooting_interval > 0.0));
+}
+
+#[test]
+fn shipped_enemy_catalog_loads() {
+    let app = loaded_app();
+    let assets = app.world.resource::<GameAssets>();
+
+    let catalogs = app.world.resource::<Assets<EnemyCatalog>>();
+    let catalog = catalogs.get(&assets.enemies).unwrap();
+    let barge = catalog.get(&EnemyType("barge".to_string())).unwrap();
+    let drone = catalog.get(&EnemyType("drone".to_string())).unwrap();
+    assert!(barge.health > drone.health);
+    assert!(barge.animation_handle.is_some());
+}
+
+#[test]
+fn shipped_waves_only_spawn_known_enemies() {
+    let app = loaded_app();
+    let assets = app.world.resource::<GameAssets>();
+
+    let enemies = app.world.resource::<Assets<EnemyCatalog>>();
+    let enemies = enemies.get(&assets.enemies).unwrap();
+    let level = app.world.resource::<CurrentLevel>();
+    let schedules = app.world.resource::<Assets<WaveSchedule>>();
+    let schedule = schedules.get(&level.waves).unwrap();
+    assert!(!schedule.waves.is_empty());
+    for wave in &schedule.waves {
+        let groups = wave.groups.iter().map(|group| &group.enemy);
+        for enemy in groups.chain(wave.boss.iter().map(|boss| &boss.enemy)) {
+            assert!(enemies.get(&EnemyType(enemy.clone())).is_some());
+        }
+    }
+}
+
+#[test]
+fn shipped_levels_have_unique_names() {
+    let app = loaded_app();
+    let assets = app.world.resource::<GameAssets>();
+
+    let registries = app.world.resource::<Assets<LevelRegistry>>();
+    let registry = registries.get(&assets.levels).unwrap();
+    assert!(registry.get(LEVEL).is_some());
+    for level in &registry.levels {
+        let same_name = registry.levels.iter().filter(|l| l.name == level.name);
+        assert_eq!(same_name.count(), 1, "{}", level.name);
+        assert!(level.money >= 0.0 && level.health > 0.0);
+    }
+}
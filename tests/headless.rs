@@ -0,0 +1,238 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+#[derive(Resource, Default)]
+struct Outcome {
+    waves_ended: u32,
+    won: bool,
+}
+
+fn record_outcome(
+    mut ev_state_update: EventReader<StateUpdateEvent>,
+    mut outcome: ResMut<Outcome>,
+) {
+    for event in ev_state_update.iter() {
+        match event {
+            StateUpdateEvent::EndWave => outcome.waves_ended += 1,
+            StateUpdateEvent::GameWon => outcome.won = true,
+            _ => {}
+        }
+    }
+}
+
+fn app_with_waves(waves: Vec<WaveDefinition>) -> App {
+    app_with_routes(waves, &[0])
+}
+
+fn app_with_routes(waves: Vec<WaveDefinition>, route_ids: &[i32]) -> App {
+    let mut app = loaded_app();
+    app.init_resource::<Outcome>().add_system(record_outcome);
+    set_waves(&mut app, waves);
+    spawn_routes(&mut app, route_ids);
+    app.update();
+    app
+}
+
+fn enemy_count(app: &mut App) -> usize {
+    app.world.query::<&Enemy>().iter(&app.world).count()
+}
+
+fn enemy_positions(app: &mut App) -> Vec<Vec3> {
+    app.world
+        .query_filtered::<&Transform, With<Enemy>>()
+        .iter(&app.world)
+        .map(|transform| transform.translation)
+        .collect()
+}
+
+fn enemies_on_route(app: &mut App, route_id: i32) -> usize {
+    app.world
+        .query::<&PathProgress>()
+        .iter(&app.world)
+        .filter(|progress| progress.route() == route_id)
+        .count()
+}
+
+#[test]
+fn wave_spawns_all_enemies_of_its_groups() {
+    let mut app = app_with_waves(vec![drones(3, 1.0)]);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 2.5);
+    assert_eq!(enemy_count(&mut app), 3);
+
+    run_for(&mut app, 2.0);
+    assert_eq!(enemy_count(&mut app), 3);
+}
+
+#[test]
+fn enemies_walk_the_path_and_leave_through_the_portal() {
+    let mut app = app_with_waves(vec![drones(2, 1.0)]);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.2);
+
+    let start = Vec3::ZERO;
+    for transform in app
+        .world
+        .query_filtered::<&Transform, With<Enemy>>()
+        .iter(&app.world)
+    {
+        assert!(transform.translation.x > start.x);
+    }
+
+    run_for(&mut app, 15.0);
+    assert_eq!(enemy_count(&mut app), 0);
+    assert_eq!(app.world.resource::<Outcome>().waves_ended, 1);
+}
+
+#[test]
+fn game_is_won_after_the_last_wave() {
+    let mut app = app_with_waves(vec![drones(1, 1.0), drones(1, 1.0)]);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 10.0);
+    assert_eq!(app.world.resource::<Outcome>().waves_ended, 1);
+    assert!(!app.world.resource::<Outcome>().won);
+    assert_eq!(game_state(&app), GameState::Building);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 10.0);
+    assert_eq!(app.world.resource::<Outcome>().waves_ended, 2);
+    assert!(app.world.resource::<Outcome>().won);
+    assert_eq!(game_state(&app), GameState::Won);
+}
+
+#[test]
+fn paused_waves_keep_enemies_in_place() {
+    let mut app = app_with_waves(vec![drones(1, 1.0)]);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+
+    app.world.send_event(StateUpdateEvent::Pause);
+    app.update();
+    let paused_at = enemy_positions(&mut app);
+    run_for(&mut app, 2.0);
+    assert_eq!(game_state(&app), GameState::Paused);
+    assert_eq!(enemy_positions(&mut app), paused_at);
+
+    // Waves only start from the building phase.
+    app.world.send_event(StateUpdateEvent::StartWave);
+    app.world.send_event(StateUpdateEvent::Resume);
+    run_for(&mut app, 1.0);
+    assert_eq!(game_state(&app), GameState::WaveRunning);
+    assert_ne!(enemy_positions(&mut app), paused_at);
+}
+
+#[test]
+fn groups_alternate_between_routes_unless_pinned() {
+    let mut pinned = drones(2, 1.0);
+    pinned.groups[0].route = Some(7);
+    let mut wave = drones(4, 1.0);
+    wave.groups.extend(pinned.groups);
+    let mut app = app_with_routes(vec![wave], &[3, 7]);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 3.5);
+    assert_eq!(enemies_on_route(&mut app, 3), 2);
+    assert_eq!(enemies_on_route(&mut app, 7), 4);
+}
+
+#[test]
+fn leaving_a_level_despawns_its_map_and_enemies() {
+    let mut app = app_with_waves(vec![drones(3, 0.1)]);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+    assert_eq!(enemy_count(&mut app), 3);
+
+    app.world.send_event(LevelEvent::Leave);
+    app.update();
+    app.update();
+    assert_eq!(game_state(&app), GameState::MainMenu);
+    assert_eq!(enemy_count(&mut app), 0);
+    let maps = app.world.query::<&PathManager>().iter(&app.world).count();
+    assert_eq!(maps, 0);
+}
+
+#[test]
+fn loading_reports_failed_assets_and_reaches_the_main_menu() {
+    let mut app = headless_app();
+    for _ in 0..1000 {
+        app.update();
+        if game_state(&app) != GameState::Loading {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(game_state(&app), GameState::MainMenu);
+
+    // Headless apps have no loaders for models and fonts.
+    let progress = app.world.resource::<LoadingProgress>();
+    assert!(progress.is_done());
+    assert!(progress.failed.contains(&"ring_a.glb#Scene0".to_string()));
+    // Files referenced by the catalogs are waited for as well.
+    for path in [
+        "enemy_observer_drone.glb#Scene0",
+        "enemy_observer_drone.glb#Animation0",
+        "projectile_gun_bullet.glb#Scene0",
+    ] {
+        assert!(progress.failed.contains(&path.to_string()), "{}", path);
+    }
+    assert!(!progress.failed.iter().any(|path| path.ends_with(".ron")));
+}
+
+#[test]
+fn levels_with_missing_files_are_left_and_reported() {
+    let mut app = menu_app();
+    let levels = app.world.resource::<GameAssets>().levels.clone();
+    let mut registries = app.world.resource_mut::<Assets<LevelRegistry>>();
+    let registry = registries.get_mut(&levels).unwrap();
+    let mut broken = registry.get(LEVEL).unwrap().clone();
+    broken.name = "Broken".to_string();
+    broken.waves = "data/missing.waves.ron".to_string();
+    registry.levels.push(broken);
+
+    app.world
+        .send_event(LevelEvent::Start("Broken".to_string()));
+    app.update();
+    for _ in 0..1000 {
+        app.update();
+        if game_state(&app) != GameState::LoadingLevel {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(game_state(&app), GameState::MainMenu);
+    let failure = app.world.resource::<LevelLoadFailure>();
+    assert_eq!(failure.level, "Broken");
+    assert_eq!(failure.failed, vec!["data/missing.waves.ron".to_string()]);
+}
+
+#[test]
+fn waves_wait_for_their_schedule_to_load() {
+    let mut app = app_with_waves(vec![drones(1, 0.0)]);
+    let waves = app.world.resource::<CurrentLevel>().waves.clone();
+    app.world
+        .resource_mut::<Assets<WaveSchedule>>()
+        .remove(&waves);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+    assert_eq!(game_state(&app), GameState::WaveRunning);
+    assert_eq!(app.world.resource::<Outcome>().waves_ended, 0);
+    assert_eq!(app.world.resource::<Bank>().ledger(), &[]);
+}
+
+#[test]
+fn an_empty_schedule_ends_the_game_without_income() {
+    let mut app = app_with_waves(vec![]);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 0.1);
+    assert_eq!(game_state(&app), GameState::Won);
+    let outcome = app.world.resource::<Outcome>();
+    assert!(outcome.won);
+    assert_eq!(outcome.waves_ended, 0);
+    assert_eq!(app.world.resource::<Bank>().ledger(), &[]);
+}
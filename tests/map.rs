@@ -0,0 +1,179 @@
+mod common;
+
+use bevy::{
+    gltf::{Gltf, GltfMesh, GltfNode, GltfPrimitive},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::RigidBody;
+use common::*;
+use towerish_side_effects::*;
+
+const VALID_MAP: &[&str] = &[
+    "Tower,0.001",
+    "Tower,1.002",
+    "Proxy,route,0,0,walk",
+    "Proxy,route,0,1,walk",
+    "Proxy,route,0,2,fall",
+    "Proxy,route,0,3,walk",
+    "Portal",
+    "Ground",
+];
+
+#[test]
+fn valid_map_has_no_issues() {
+    let report = validate_map(VALID_MAP.iter().copied());
+    assert!(report.is_ok(), "{:?}", report);
+}
+
+#[test]
+fn all_naming_errors_are_collected() {
+    let names = [
+        "Tower,2.003",
+        "Tower_without_type",
+        "Proxy,route,0,0,walk",
+        "Proxy,route,0,0,walk.001",
+        "Proxy,route,0,3,walk",
+        "Proxy,route,1,0",
+    ];
+    let report = validate_map(names);
+
+    assert_eq!(report.issues.len(), 6, "{:?}", report);
+    assert!(report.issues.iter().any(|issue| matches!(
+        issue,
+        MapIssue::BadTowerBase { node, .. } if node == "Tower,2.003"
+    )));
+    assert!(report.rejects("Tower_without_type"));
+    assert!(report.rejects("Proxy,route,1,0"));
+    assert!(report.rejects("Proxy,route,0,0,walk.001"));
+    assert!(!report.rejects("Proxy,route,0,0,walk"));
+    assert!(report.issues.contains(&MapIssue::NodeGap {
+        route_id: 0,
+        after: 0,
+        next: 3,
+    }));
+    assert_eq!(report.issues.last(), Some(&MapIssue::MissingPortal));
+}
+
+/// Stores a GLTF with a node for each name, nodes in `with_mesh` get a cube.
+fn set_gltf(
+    app: &mut App,
+    handle: &Handle<Gltf>,
+    names: &[&str],
+    with_mesh: &[&str],
+) {
+    let mesh = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(shape::Cube::default().into());
+    let gltf_mesh =
+        app.world.resource_mut::<Assets<GltfMesh>>().add(GltfMesh {
+            primitives: vec![GltfPrimitive {
+                mesh,
+                material: None,
+                extras: None,
+                material_extras: None,
+            }],
+            extras: None,
+        });
+    let named_nodes = names
+        .iter()
+        .map(|name| {
+            let node = GltfNode {
+                children: vec![],
+                mesh: with_mesh.contains(name).then(|| gltf_mesh.clone()),
+                transform: Transform::default(),
+                extras: None,
+            };
+            let node = app.world.resource_mut::<Assets<GltfNode>>().add(node);
+            (name.to_string(), node)
+        })
+        .collect();
+    let default_scene = Some(Handle::default());
+    app.world.resource_mut::<Assets<Gltf>>().set_untracked(
+        handle,
+        Gltf {
+            scenes: vec![],
+            named_scenes: default(),
+            meshes: vec![],
+            named_meshes: default(),
+            materials: vec![],
+            named_materials: default(),
+            nodes: vec![],
+            named_nodes,
+            default_scene,
+            animations: vec![],
+            named_animations: default(),
+        },
+    );
+}
+
+#[test]
+fn map_spawns_its_route_and_terrain_colliders() {
+    let mut app = loaded_app();
+    let level = app.world.resource::<CurrentLevel>();
+    let (map, collision) =
+        (level.map.clone(), level.collision.clone().unwrap());
+    set_gltf(
+        &mut app,
+        &map,
+        &[
+            "Proxy,route,0,0,walk",
+            "Proxy,route,0,1,walk",
+            "Portal",
+            "Ground_col",
+        ],
+        &["Ground_col"],
+    );
+    set_gltf(
+        &mut app,
+        &collision,
+        &["Rock_convex", "Empty"],
+        &["Rock_convex"],
+    );
+    app.update();
+    app.update();
+
+    let report = app.world.resource::<MapLoadReport>();
+    assert!(report.is_ok(), "{:?}", report);
+    let path = app.world.query::<&PathManager>().single(&app.world);
+    assert_eq!(path.route(0).unwrap().waypoints.len(), 2);
+    let colliders = app
+        .world
+        .query::<&RigidBody>()
+        .iter(&app.world)
+        .filter(|body| **body == RigidBody::Fixed)
+        .count();
+    assert_eq!(colliders, 2);
+}
+
+#[test]
+fn maps_spawn_without_a_collision_scene_that_failed_to_load() {
+    let mut app = loaded_app();
+    let map = app.world.resource::<CurrentLevel>().map.clone();
+    // Headless apps have no GLTF loader, so the collision scene fails.
+    set_gltf(
+        &mut app,
+        &map,
+        &["Proxy,route,0,0,walk", "Proxy,route,0,1,walk", "Portal"],
+        &[],
+    );
+    app.update();
+    app.update();
+
+    let report = app.world.resource::<MapLoadReport>();
+    assert_eq!(
+        report.issues,
+        vec![MapIssue::MissingCollision {
+            path: "map_a_collision.glb".to_string()
+        }]
+    );
+    assert_eq!(
+        app.world.query::<&PathManager>().iter(&app.world).count(),
+        1
+    );
+
+    app.world.send_event(LevelEvent::Leave);
+    app.update();
+    app.update();
+    assert!(app.world.resource::<MapLoadReport>().is_ok());
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn proxy(
+    node_id: i32,
+    kind: ProxyKind,
+    movement_type: MovementType,
+    location: Vec3,
+) -> Proxy {
+    Proxy {
+        route_id: 0,
+        node_id,
+        kind,
+        movement_type,
+        location,
+    }
+}
+
+fn walk(node_id: i32, location: Vec3) -> Proxy {
+    proxy(node_id, ProxyKind::Route, MovementType::Walking, location)
+}
+
+fn enemy_position(app: &mut App) -> Vec3 {
+    app.world
+        .query_filtered::<&Transform, With<Enemy>>()
+        .single(&app.world)
+        .translation
+}
+
+#[test]
+fn proxies_are_parsed_from_node_names() {
+    let proxy = Proxy::from_str("Proxy,portal,2,7,fall.001").unwrap();
+    assert_eq!(proxy.kind, ProxyKind::Portal);
+    assert_eq!(proxy.movement_type, MovementType::Falling);
+    assert_eq!((proxy.route_id, proxy.node_id), (2, 7));
+
+    assert!(Proxy::from_str("Proxy,route,0,1").is_err());
+    assert!(Proxy::from_str("Proxy,tunnel,0,1,walk").is_err());
+}
+
+#[test]
+fn portals_skip_the_segment_to_the_next_waypoint() {
+    let mut route = RoutePath::default();
+    route.push(walk(0, Vec3::ZERO));
+    route.push(proxy(
+        1,
+        ProxyKind::Portal,
+        MovementType::Walking,
+        Vec3::X * 2.0,
+    ));
+    route.push(walk(2, Vec3::new(2.0, 0.0, 50.0)));
+    route.push(walk(3, Vec3::new(4.0, 0.0, 50.0)));
+
+    assert!((route.length() - 4.0).abs() < 1e-4);
+    assert!(route.get_position(1.0).unwrap().distance(Vec3::X) < 1e-4);
+    let exit = route.get_position(2.0).unwrap();
+    assert!(exit.distance(Vec3::new(2.0, 0.0, 50.0)) < 1e-4);
+    let walked = route.get_position(3.0).unwrap();
+    assert!(walked.distance(Vec3::new(3.0, 0.0, 50.0)) < 1e-4);
+}
+
+#[test]
+fn routes_without_waypoints_have_no_position() {
+    let route = RoutePath::default();
+    assert_eq!(route.get_position(0.0), None);
+    assert!(route.fall_between(0.0, 1.0).is_none());
+}
+
+#[test]
+fn routes_curve_smoothly_through_their_waypoints() {
+    let mut route = RoutePath::default();
+    route.push(walk(0, Vec3::ZERO));
+    route.push(walk(1, Vec3::new(10.0, 0.0, 0.0)));
+    route.push(walk(2, Vec3::new(10.0, 0.0, 10.0)));
+
+    // Longer than the straight segments but shorter than going around the
+    // corner twice.
+    assert!(route.length() > 20.0 && route.length() < 22.0);
+    let half = route.get_position(route.length() / 2.0).unwrap();
+    assert!(half.distance(Vec3::new(10.0, 0.0, 0.0)) < 1.0, "{}", half);
+    assert!(
+        route
+            .get_position(route.length())
+            .unwrap()
+            .distance(Vec3::new(10.0, 0.0, 10.0))
+            < 1e-4
+    );
+
+    assert!(route.get_tangent(0.0).distance(Vec3::X) < 0.1);
+    assert!(route.get_tangent(route.length()).distance(Vec3::Z) < 0.1);
+    let corner = route.get_tangent(route.length() / 2.0);
+    assert!(corner.x > 0.1 && corner.z > 0.1, "{}", corner);
+}
+
+#[test]
+fn enemies_face_their_walking_direction() {
+    let mut app = loaded_app();
+    spawn_straight_path(&mut app);
+    set_waves(&mut app, vec![drones(1, 1.0)]);
+    app.update();
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+    let rotation = app
+        .world
+        .query_filtered::<&Transform, With<Enemy>>()
+        .single(&app.world)
+        .rotation;
+    assert!((rotation * Vec3::NEG_Z).distance(Vec3::X) < 1e-4);
+}
+
+#[test]
+fn enemies_fall_to_the_next_waypoint_and_walk_on() {
+    let mut app = loaded_app();
+    let mut path = PathManager::new();
+    path.push(walk(0, Vec3::new(0.0, 4.0, 0.0)));
+    path.push(proxy(
+        1,
+        ProxyKind::Route,
+        MovementType::Falling,
+        Vec3::new(1.0, 4.0, 0.0),
+    ));
+    path.push(walk(2, Vec3::new(3.0, 0.0, 0.0)));
+    path.push(walk(3, Vec3::new(13.0, 0.0, 0.0)));
+    app.world.spawn(path);
+    set_waves(&mut app, vec![drones(1, 1.0)]);
+    app.update();
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+    let falling = app
+        .world
+        .query_filtered::<(), (With<Enemy>, With<Falling>)>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(falling, 1);
+    let position = enemy_position(&mut app);
+    assert!(position.y < 4.0 && position.y > 0.0, "{}", position);
+
+    // Falling 4 units takes about 2.8 seconds with a gravity of 1
+    run_for(&mut app, 3.0);
+    let position = enemy_position(&mut app);
+    assert_eq!(position.y, 0.0);
+    assert!(position.x > 3.0, "{}", position);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn health(app: &App) -> f32 {
+    app.world.resource::<PlayerBase>().health()
+}
+
+#[test]
+fn healing_is_paid_by_the_bank() {
+    let mut base = PlayerBase::new(2.0);
+    let mut bank = Bank::new(HEAL_PRICE + 10.0, 1);
+    assert!(base.heal(&mut bank));
+    assert_eq!(base.health(), 2.0 + HEAL_AMOUNT);
+    assert!(!base.heal(&mut bank));
+    assert_eq!(bank.balance(), 10.0);
+}
+
+#[test]
+fn destroyed_bases_cannot_be_healed() {
+    let mut base = PlayerBase::new(1.0);
+    base.damage(1.0);
+    assert!(base.is_destroyed());
+    assert!(!base.heal(&mut Bank::new(1000.0, 1)));
+}
+
+#[test]
+fn barges_hurt_more_than_drones() {
+    let mut app = loaded_app();
+    let mut barges = drones(1, 0.0);
+    barges.groups[0].enemy = "barge".to_string();
+    set_waves(&mut app, vec![drones(1, 0.0), barges]);
+    spawn_straight_path(&mut app);
+    let start = health(&app);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 10.0);
+    assert_eq!(game_state(&app), GameState::Building);
+    let drone_damage = start - health(&app);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 15.0);
+    let barge_damage = start - drone_damage - health(&app);
+    assert!(barge_damage > drone_damage);
+}
+
+#[test]
+fn the_level_is_lost_once_the_base_is_destroyed() {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(3, 0.5)]);
+    spawn_straight_path(&mut app);
+    app.insert_resource(PlayerBase::new(2.0));
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 8.0);
+    assert_eq!(health(&app), 0.0);
+    assert_eq!(game_state(&app), GameState::Lost);
+}
+
+#[test]
+fn destroying_the_base_with_the_last_enemy_loses_the_level() {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(1, 0.0)]);
+    spawn_straight_path(&mut app);
+    app.insert_resource(PlayerBase::new(1.0));
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    for _ in 0..1000 {
+        app.update();
+        assert_ne!(game_state(&app), GameState::Won);
+    }
+    assert_eq!(game_state(&app), GameState::Lost);
+    let income = app
+        .world
+        .resource::<Bank>()
+        .ledger()
+        .iter()
+        .any(|transaction| transaction.source == TransactionSource::WaveIncome);
+    assert!(!income);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use common::*;
+use towerish_side_effects::*;
+
+const TOWER: Vec3 = Vec3::new(5.0, 0.0, 2.0);
+
+/// Updates until the started level is entered and provides its map with a
+/// tower base, returns the base.
+fn enter_recorded_level(app: &mut App) -> Entity {
+    enter_level(app);
+    set_waves(app, vec![drones(2, 0.5), drones(3, 0.5), drones(4, 0.5)]);
+    spawn_straight_path(app);
+    let base = app
+        .world
+        .spawn((
+            TowerBase::Normal("a".to_string()),
+            TransformBundle::from_transform(Transform::from_translation(TOWER)),
+        ))
+        .id();
+    app.update();
+    base
+}
+
+/// Makes every other frame longer, like a game that runs unevenly.
+fn stutter(mut strategy: ResMut<TimeUpdateStrategy>, mut long: Local<bool>) {
+    *long = !*long;
+    if let TimeUpdateStrategy::ManualInstant(instant) = strategy.as_mut() {
+        if *long {
+            *instant += Duration::from_millis(7);
+        }
+    }
+}
+
+fn run_until_building(app: &mut App) {
+    for _ in 0..2000 {
+        app.update();
+        if game_state(app) == GameState::Building {
+            return;
+        }
+    }
+    panic!("wave did not end in {:?}", game_state(app));
+}
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("towerish-{}-{}.ron", name, std::process::id()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn snapshot(app: &mut App, name: &str) -> SaveGame {
+    let slot = SaveSlot(temp_path(name));
+    app.insert_resource(slot.clone());
+    app.world.send_event(SaveEvent::Save);
+    app.update();
+    let save = SaveGame::read(&slot).expect("game was saved");
+    std::fs::remove_file(&slot.0).ok();
+    save
+}
+
+#[test]
+fn replays_reproduce_the_recorded_game() {
+    let mut app = menu_app();
+    app.insert_resource(RngSeed(Some(11)))
+        .add_system(stutter.in_base_set(CoreSet::First).before(TimeSystem));
+    app.world.send_event(LevelEvent::Start(LEVEL.to_string()));
+    let base = enter_recorded_level(&mut app);
+
+    let tower = build_tower_on(&mut app, base, "gun", TOWER).unwrap();
+    run_for(&mut app, 0.5);
+    for _ in 0..3 {
+        app.world.send_event(TowerBuildEvent::Upgrade {
+            entity: tower,
+            effect: TowerUpgrades::ForceBuff(20.0),
+            side_effect: UpgradeSideEffect::Roll,
+        });
+        app.update();
+    }
+    app.world.send_event(StateUpdateEvent::StartWave);
+    app.update();
+    run_until_building(&mut app);
+    app.world.send_event(StateUpdateEvent::Heal);
+    run_for(&mut app, 0.2);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    app.update();
+    run_until_building(&mut app);
+    let frames = app.world.resource::<ReplayRecorder>().frame();
+    let recorded = snapshot(&mut app, "recorded");
+
+    let replay = app.world.resource::<ReplayRecorder>().replay().clone();
+    assert_eq!(replay.seed, 11);
+    assert_eq!(replay.commands.len(), 7);
+    let replay = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+
+    let mut played = menu_app();
+    played.insert_resource(ReplayPlayer::new(replay));
+    enter_recorded_level(&mut played);
+    while played.world.resource::<ReplayRecorder>().frame() < frames {
+        played.update();
+    }
+    assert!(played.world.resource::<ReplayPlayer>().is_finished());
+    assert_eq!(snapshot(&mut played, "played"), recorded);
+}
+
+#[test]
+fn levels_loaded_from_a_save_are_not_recorded() {
+    let mut app = loaded_app();
+    spawn_straight_path(&mut app);
+    app.update();
+    assert!(app.world.resource::<ReplayRecorder>().is_recording());
+    let saved = snapshot(&mut app, "loaded-save");
+    let slot = SaveSlot(temp_path("loaded-save"));
+    saved.write(&slot).unwrap();
+
+    app.insert_resource(slot.clone());
+    app.world.send_event(SaveEvent::Load);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(game_state(&app), GameState::Building);
+    assert!(!app.world.resource::<ReplayRecorder>().is_recording());
+
+    let replay = ReplaySlot(temp_path("loaded-replay"));
+    app.insert_resource(replay.clone());
+    app.world.send_event(ReplayEvent::Save);
+    app.update();
+    assert!(Replay::read(&replay).is_err());
+    std::fs::remove_file(&slot.0).ok();
+}
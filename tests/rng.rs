@@ -0,0 +1,53 @@
+mod common;
+
+use common::*;
+use towerish_side_effects::*;
+
+fn roll_side_effects(
+    rng: &mut GameRng,
+    count: usize,
+) -> Vec<Option<TowerSideEffects>> {
+    (0..count)
+        .map(|_| TowerSideEffects::roll(rng, 5, 10))
+        .collect()
+}
+
+#[test]
+fn side_effects_are_reproducible_for_a_seed() {
+    let mut rng = GameRng::from_seed(42);
+    let outcomes = roll_side_effects(&mut rng, 1000);
+    let without = outcomes.iter().filter(|outcome| outcome.is_none()).count();
+    assert_eq!(without, 583);
+    assert_eq!(
+        roll_side_effects(&mut GameRng::from_seed(42), 1000),
+        outcomes
+    );
+}
+
+#[test]
+fn negative_forces_roll_no_side_effect() {
+    let mut rng = GameRng::from_seed(42);
+    for _ in 0..100 {
+        assert_eq!(TowerSideEffects::roll(&mut rng, 5, -10), None);
+    }
+}
+
+#[test]
+fn restored_rng_continues_the_sequence() {
+    let mut rng = GameRng::from_seed(3);
+    roll_side_effects(&mut rng, 10);
+    let mut restored = GameRng::restore(rng.seed(), rng.word_pos());
+    assert_eq!(
+        roll_side_effects(&mut restored, 100),
+        roll_side_effects(&mut rng, 100)
+    );
+}
+
+#[test]
+fn levels_start_with_the_configured_seed() {
+    let mut app = menu_app();
+    app.insert_resource(RngSeed(Some(9)));
+    app.world.send_event(LevelEvent::Start(LEVEL.to_string()));
+    enter_level(&mut app);
+    assert_eq!(app.world.resource::<GameRng>().seed(), 9);
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn temp_slot(name: &str) -> SaveSlot {
+    let path = std::env::temp_dir().join(format!(
+        "towerish-{}-{}.ron",
+        name,
+        std::process::id()
+    ));
+    SaveSlot(path.to_string_lossy().into_owned())
+}
+
+fn save(app: &mut App, slot: &SaveSlot) -> SaveGame {
+    app.insert_resource(slot.clone());
+    app.world.send_event(SaveEvent::Save);
+    app.update();
+    SaveGame::read(slot).expect("game was saved")
+}
+
+fn towers(app: &mut App) -> Vec<(Vec3, Vec<TowerUpgrades>)> {
+    app.world
+        .query::<(&Tower, &Transform)>()
+        .iter(&app.world)
+        .map(|(tower, transform)| {
+            (transform.translation, tower.upgrades.clone())
+        })
+        .collect()
+}
+
+#[test]
+fn save_game_round_trips_through_ron() {
+    let save = SaveGame {
+        level: LEVEL.to_string(),
+        money: 250.0,
+        health: 3.0,
+        enemies_killed: 12,
+        waves_finished: 2,
+        wave_count: 3,
+        towers: vec![TowerSave {
+            kind: "cannon".to_string(),
+            position: Vec3::new(1.0, 2.0, 3.0),
+            upgrades: vec![TowerUpgrades::AOE(2.0)],
+            side_effects: vec![TowerSideEffects::HealShot(2.0)],
+            targeting: TargetingMode::Strongest,
+            spent: 350.0,
+        }],
+        seed: 7,
+        rng_word_pos: 16,
+    };
+    let text = save.to_ron().unwrap();
+    assert_eq!(SaveGame::from_ron(&text).unwrap(), save);
+}
+
+#[test]
+fn saved_towers_and_waves_are_restored() {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(1, 0.0), drones(1, 0.0)]);
+    spawn_straight_path(&mut app);
+    app.update();
+
+    let pos = Vec3::new(5.0, 0.0, 3.0);
+    let tower = build_tower(&mut app, "gun", pos);
+    app.world.send_event(TowerBuildEvent::Upgrade {
+        entity: tower,
+        effect: TowerUpgrades::ForceBuff(2.0),
+        side_effect: UpgradeSideEffect::Fixed(Some(
+            TowerSideEffects::WeakShot(2.0),
+        )),
+    });
+    app.world.send_event(TowerBuildEvent::SetTargeting {
+        entity: tower,
+        mode: TargetingMode::Weakest,
+    });
+    app.update();
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    app.update();
+    app.update();
+    assert_eq!(game_state(&app), GameState::WaveRunning);
+    for _ in 0..2000 {
+        app.update();
+        if game_state(&app) == GameState::Building {
+            break;
+        }
+    }
+    assert_eq!(game_state(&app), GameState::Building);
+
+    let slot = temp_slot("restored");
+    let saved = save(&mut app, &slot);
+    assert_eq!(saved.level, LEVEL);
+    assert_eq!(saved.waves_finished, 1);
+    assert_eq!(saved.wave_count, 2);
+    assert_eq!(saved.towers.len(), 1);
+    assert_eq!(saved.towers[0].position, pos);
+    assert_eq!(
+        saved.towers[0].upgrades,
+        vec![TowerUpgrades::ForceBuff(2.0)]
+    );
+    assert_eq!(
+        saved.towers[0].side_effects,
+        vec![TowerSideEffects::WeakShot(2.0)]
+    );
+    assert_eq!(saved.towers[0].targeting, TargetingMode::Weakest);
+
+    let mut restored = menu_app();
+    restored.insert_resource(slot.clone());
+    restored.world.send_event(SaveEvent::Load);
+    for _ in 0..10 {
+        restored.update();
+    }
+    assert_eq!(game_state(&restored), GameState::Building);
+    spawn_straight_path(&mut restored);
+    restored.update();
+    restored.update();
+
+    assert_eq!(towers(&mut restored), towers(&mut app));
+    assert_eq!(save(&mut restored, &temp_slot("resaved")), saved);
+    std::fs::remove_file(&slot.0).ok();
+    std::fs::remove_file(&temp_slot("resaved").0).ok();
+}
+
+#[test]
+fn loading_from_a_level_restarts_the_saved_level() {
+    let slot = temp_slot("from-level");
+    let mut app = loaded_app();
+    spawn_straight_path(&mut app);
+    app.update();
+    let saved = save(&mut app, &slot);
+
+    app.world.send_event(StateUpdateEvent::StartWave);
+    app.update();
+    app.world.send_event(SaveEvent::Load);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(game_state(&app), GameState::Building);
+    spawn_straight_path(&mut app);
+    app.update();
+    app.update();
+    assert_eq!(save(&mut app, &temp_slot("from-level-resaved")), saved);
+    std::fs::remove_file(&slot.0).ok();
+    std::fs::remove_file(&temp_slot("from-level-resaved").0).ok();
+}
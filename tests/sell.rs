@@ -0,0 +1,155 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn spawn_base(app: &mut App, name: &str, position: Vec3) -> Entity {
+    app.world
+        .spawn((
+            TowerBase::Normal(name.to_string()),
+            TransformBundle::from_transform(Transform::from_translation(
+                position,
+            )),
+        ))
+        .id()
+}
+
+fn bases(app: &mut App) -> Vec<(String, Vec3)> {
+    app.world
+        .query::<(&TowerBase, &GlobalTransform)>()
+        .iter(&app.world)
+        .map(|(base, transform)| (base.to_string(), transform.translation()))
+        .collect()
+}
+
+#[test]
+fn selling_refunds_part_of_the_spend_and_restores_the_base() {
+    let mut app = loaded_app();
+    let position = Vec3::new(2.0, 1.0, 0.0);
+    let base = spawn_base(&mut app, "a", position);
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", position).unwrap();
+    assert!(bases(&mut app).is_empty());
+
+    app.world.send_event(TowerBuildEvent::Upgrade {
+        entity: gun,
+        effect: TowerUpgrades::ForceBuff(1.0),
+        side_effect: UpgradeSideEffect::Fixed(None),
+    });
+    app.update();
+    let tower = app.world.get::<Tower>(gun).unwrap();
+    let upgrade_price = TowerUpgrades::ForceBuff(1.0).get_price(0, 1);
+    assert!(tower.spent > upgrade_price);
+    assert_eq!(tower.refund(), tower.spent * SELL_REFUND);
+
+    app.world.send_event(TowerBuildEvent::Sell { entity: gun });
+    app.update();
+    assert!(app.world.get_entity(gun).is_none());
+    assert_eq!(bases(&mut app), vec![("a".to_string(), position)]);
+}
+
+#[test]
+fn relocating_swaps_the_tower_bases() {
+    let mut app = loaded_app();
+    let from = Vec3::new(2.0, 1.0, 0.0);
+    let to = Vec3::new(8.0, 1.0, 4.0);
+    let base = spawn_base(&mut app, "a", from);
+    let target = spawn_base(&mut app, "b", to);
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", from).unwrap();
+
+    app.world.send_event(TowerBuildEvent::Relocate {
+        entity: gun,
+        base: target,
+    });
+    app.update();
+    app.update();
+    assert_eq!(app.world.get::<Transform>(gun).unwrap().translation, to);
+    assert_eq!(bases(&mut app), vec![("a".to_string(), from)]);
+}
+
+#[test]
+fn events_for_a_tower_sold_in_the_same_frame_are_dropped() {
+    let mut app = loaded_app();
+    let position = Vec3::new(2.0, 1.0, 0.0);
+    let base = spawn_base(&mut app, "a", position);
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", position).unwrap();
+    let balance = app.world.resource::<Bank>().balance();
+
+    app.world.send_event(TowerBuildEvent::Sell { entity: gun });
+    app.world.send_event(TowerBuildEvent::Sell { entity: gun });
+    app.world.send_event(TowerBuildEvent::SetTargeting {
+        entity: gun,
+        mode: TargetingMode::Strongest,
+    });
+    let not_a_tower = app.world.spawn_empty().id();
+    app.world.send_event(TowerBuildEvent::SetTargeting {
+        entity: not_a_tower,
+        mode: TargetingMode::Strongest,
+    });
+    app.update();
+
+    assert!(app.world.get_entity(gun).is_none());
+    assert!(app.world.get::<TargetingMode>(not_a_tower).is_none());
+    let refunds = app.world.resource::<Bank>().balance() - balance;
+    assert_eq!(
+        refunds,
+        app.world.resource::<Bank>().ledger().last().unwrap().amount
+    );
+}
+
+#[test]
+fn events_for_a_base_used_in_the_same_frame_are_dropped() {
+    let mut app = loaded_app();
+    let from = Vec3::new(2.0, 1.0, 0.0);
+    let to = Vec3::new(8.0, 1.0, 4.0);
+    let base = spawn_base(&mut app, "a", from);
+    let target = spawn_base(&mut app, "b", to);
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", from).unwrap();
+
+    for _ in 0..2 {
+        app.world.send_event(TowerBuildEvent::Dispatch {
+            entity: target,
+            kind: TowerType("gun".to_string()),
+            pos: to,
+        });
+    }
+    app.world.send_event(TowerBuildEvent::Relocate {
+        entity: gun,
+        base: target,
+    });
+    app.update();
+
+    let towers = app.world.query::<&Tower>().iter(&app.world).count();
+    assert_eq!(towers, 2);
+    assert_eq!(app.world.get::<Transform>(gun).unwrap().translation, from);
+    let builds = app
+        .world
+        .resource::<Bank>()
+        .ledger()
+        .iter()
+        .filter(|transaction| transaction.source == TransactionSource::Build)
+        .count();
+    assert_eq!(builds, 2);
+}
+
+#[test]
+fn restored_bases_are_despawned_with_the_level() {
+    let mut app = loaded_app();
+    let position = Vec3::new(2.0, 1.0, 0.0);
+    let base = spawn_base(&mut app, "a", position);
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", position).unwrap();
+    app.world.send_event(TowerBuildEvent::Sell { entity: gun });
+    app.update();
+    assert_eq!(bases(&mut app).len(), 1);
+
+    app.world.send_event(LevelEvent::Leave);
+    app.update();
+    app.update();
+    assert_eq!(game_state(&app), GameState::MainMenu);
+    assert!(bases(&mut app).is_empty());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn upgrade(
+    app: &mut App,
+    entity: Entity,
+    effect: TowerUpgrades,
+    side_effect: Option<TowerSideEffects>,
+) {
+    app.world.send_event(TowerBuildEvent::Upgrade {
+        entity,
+        effect,
+        side_effect: UpgradeSideEffect::Fixed(side_effect),
+    });
+    app.update();
+}
+
+fn upgrades(app: &App, entity: Entity) -> Vec<TowerUpgrades> {
+    app.world.get::<Tower>(entity).unwrap().upgrades.clone()
+}
+
+#[test]
+fn revert_upgrade_takes_back_the_previous_upgrade() {
+    let mut app = loaded_app();
+    let gun = build_tower(&mut app, "gun", Vec3::ZERO);
+    upgrade(&mut app, gun, TowerUpgrades::ForceBuff(5.0), None);
+    upgrade(
+        &mut app,
+        gun,
+        TowerUpgrades::RangeBuff(2.0),
+        Some(TowerSideEffects::RevertUpgrade),
+    );
+    assert_eq!(upgrades(&app, gun), vec![TowerUpgrades::RangeBuff(2.0)]);
+}
+
+#[test]
+fn friendly_fire_hits_adjacent_towers() {
+    let mut app = loaded_app();
+    app.insert_resource(Bank::new(10_000.0, 1));
+    let guns = [
+        build_tower(&mut app, "gun", Vec3::ZERO),
+        build_tower(&mut app, "gun", Vec3::new(3.0, 0.0, 0.0)),
+        build_tower(&mut app, "gun", Vec3::new(20.0, 0.0, 0.0)),
+    ];
+    for gun in &guns {
+        upgrade(&mut app, *gun, TowerUpgrades::ForceBuff(5.0), None);
+    }
+    upgrade(
+        &mut app,
+        guns[0],
+        TowerUpgrades::ForceBuff(5.0),
+        Some(TowerSideEffects::FriendlyFire),
+    );
+    assert_eq!(upgrades(&app, guns[0]).len(), 2);
+    assert!(upgrades(&app, guns[1]).is_empty());
+    assert_eq!(upgrades(&app, guns[2]).len(), 1);
+}
+
+#[test]
+fn explode_destroys_the_tower() {
+    let mut app = loaded_app();
+    let base = app
+        .world
+        .spawn((
+            TowerBase::Normal("a".to_string()),
+            TransformBundle::default(),
+        ))
+        .id();
+    app.update();
+    let gun = build_tower_on(&mut app, base, "gun", Vec3::ZERO).unwrap();
+    upgrade(
+        &mut app,
+        gun,
+        TowerUpgrades::ForceBuff(5.0),
+        Some(TowerSideEffects::Explode),
+    );
+    assert!(app.world.get_entity(gun).is_none());
+    let bases = app.world.query::<&TowerBase>().iter(&app.world).count();
+    assert_eq!(bases, 1);
+}
+
+#[test]
+fn only_hit_side_effects_reach_the_enemies() {
+    let tower = Tower {
+        side_effects: vec![
+            TowerSideEffects::WeakShot(2.0),
+            TowerSideEffects::Jam(3.0),
+            TowerSideEffects::HealShot(1.0),
+            TowerSideEffects::Misfire(10.0),
+        ],
+        ..default()
+    };
+    assert_eq!(
+        SideEffectBundle::from_tower(&tower).side_effects,
+        vec![
+            TowerSideEffects::WeakShot(2.0),
+            TowerSideEffects::HealShot(1.0)
+        ]
+    );
+}
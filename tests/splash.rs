@@ -0,0 +1,76 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use common::*;
+use towerish_side_effects::*;
+
+fn projectile(aoe_radius: f32) -> Projectile {
+    Projectile {
+        force: 10.0,
+        aoe_radius,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn splash_falls_off_with_distance() {
+    let projectile = projectile(4.0);
+    assert_eq!(projectile.splash_force(0.0), 10.0);
+    assert_eq!(projectile.splash_force(1.0), 7.5);
+    assert_eq!(projectile.splash_force(2.0), 5.0);
+    assert_eq!(projectile.splash_force(4.0), 0.0);
+    assert_eq!(projectile.splash_force(6.0), 0.0);
+}
+
+#[test]
+fn no_splash_without_aoe_upgrades() {
+    assert_eq!(projectile(0.0).splash_force(0.0), 0.0);
+}
+
+#[test]
+fn splash_hits_the_enemies_around_the_impact() {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(3, 1.0)]);
+    spawn_straight_path(&mut app);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 2.1);
+    // Drones walk 1.5 per second, so they are 1.5 apart
+    let mut drones = app
+        .world
+        .query_filtered::<(Entity, &GlobalTransform), With<Enemy>>()
+        .iter(&app.world)
+        .map(|(entity, transform)| (entity, transform.translation()))
+        .collect::<Vec<_>>();
+    drones.sort_by(|a, b| b.1.x.total_cmp(&a.1.x));
+    let [(first, impact), (second, _), (third, _)] = drones[..] else {
+        panic!("expected three drones, got {}", drones.len());
+    };
+
+    let projectile = app
+        .world
+        .spawn((
+            projectile(2.0),
+            SideEffectBundle {
+                side_effects: vec![],
+            },
+            GlobalTransform::from_translation(impact),
+        ))
+        .id();
+    app.world.send_event(ImpactEvent {
+        projectile,
+        entity: first,
+    });
+    let mut hits = ManualEventReader::<HitEvent>::default();
+    app.update();
+
+    let hits = hits
+        .iter(app.world.resource::<Events<HitEvent>>())
+        .map(|hit| (hit.entity, hit.force))
+        .collect::<Vec<_>>();
+    assert_eq!(hits.len(), 2, "{:?}", hits);
+    assert_eq!(hits[0], (first, 10.0));
+    assert_eq!(hits[1].0, second);
+    assert!(hits[1].1 > 0.0 && hits[1].1 < 10.0);
+    assert!(hits.iter().all(|(entity, _)| *entity != third));
+    assert!(app.world.get_entity(projectile).is_none());
+}
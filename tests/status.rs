@@ -0,0 +1,102 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn timed(effect: StatusEffect, seconds: f32) -> TimedStatusEffect {
+    TimedStatusEffect { effect, seconds }
+}
+
+#[test]
+fn the_strongest_slow_applies_and_stuns_stop_enemies() {
+    let mut effects = StatusEffects::default();
+    effects.apply(timed(StatusEffect::Slow(0.5), 2.0));
+    effects.apply(timed(StatusEffect::Slow(0.25), 2.0));
+    assert_eq!(effects.speed_multiplier(), 0.5);
+    effects.apply(timed(StatusEffect::Stun, 0.5));
+    assert_eq!(effects.speed_multiplier(), 0.0);
+    effects.tick(1.0);
+    assert_eq!(effects.speed_multiplier(), 0.5);
+    effects.tick(1.0);
+    assert_eq!(effects.speed_multiplier(), 1.0);
+    assert!(effects.effects().is_empty());
+}
+
+#[test]
+fn poison_stacks_and_burns_refresh() {
+    let mut effects = StatusEffects::default();
+    effects.apply(timed(StatusEffect::Burn(1.0), 3.0));
+    effects.apply(timed(StatusEffect::Burn(2.0), 3.0));
+    effects.apply(timed(StatusEffect::Poison(0.5), 5.0));
+    effects.apply(timed(StatusEffect::Poison(0.5), 5.0));
+    assert_eq!(effects.damage_per_second(), 3.0);
+}
+
+#[test]
+fn only_status_upgrades_have_status_effects() {
+    assert!(TowerUpgrades::ForceBuff(5.0).status_effect().is_none());
+    let frost = TowerUpgrades::FrostRounds(10.0).status_effect().unwrap();
+    assert_eq!(frost.effect, StatusEffect::Slow(0.5));
+}
+
+#[test]
+fn upgrades_of_the_same_kind_add_up() {
+    let tower = Tower {
+        upgrades: vec![
+            TowerUpgrades::Incendiary(10.0),
+            TowerUpgrades::ForceBuff(5.0),
+            TowerUpgrades::Incendiary(2.0),
+            TowerUpgrades::ConcussionRounds(10.0),
+            TowerUpgrades::ConcussionRounds(5.0),
+        ],
+        ..default()
+    };
+    assert_eq!(
+        tower.status_effects(),
+        vec![
+            TowerUpgrades::Incendiary(12.0).status_effect().unwrap(),
+            TowerUpgrades::ConcussionRounds(15.0)
+                .status_effect()
+                .unwrap(),
+        ]
+    );
+}
+
+fn drone_hit_with(status: TimedStatusEffect) -> (App, Entity) {
+    let (mut app, drone) = spawn_single_drone();
+    app.world.send_event(HitEvent {
+        entity: drone,
+        tower: None,
+        force: 0.0,
+        damage_type: DamageType::Kinetic,
+        status_effects: vec![status],
+        side_effects: vec![],
+    });
+    (app, drone)
+}
+
+fn x(app: &App, entity: Entity) -> f32 {
+    app.world.get::<Transform>(entity).unwrap().translation.x
+}
+
+#[test]
+fn slowed_enemies_walk_slower() {
+    let (mut app, drone) = drone_hit_with(timed(StatusEffect::Slow(0.5), 5.0));
+    app.update();
+    let start = x(&app, drone);
+    run_for(&mut app, 1.0);
+    let slowed = x(&app, drone) - start;
+    run_for(&mut app, 5.0);
+    let start = x(&app, drone);
+    run_for(&mut app, 1.0);
+    assert!(slowed < 0.6 * (x(&app, drone) - start));
+}
+
+#[test]
+fn poison_kills_over_time() {
+    let (mut app, drone) =
+        drone_hit_with(timed(StatusEffect::Poison(10.0), 1.0));
+    run_for(&mut app, 0.5);
+    assert!(app.world.get_entity(drone).is_none());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use towerish_side_effects::*;
+
+fn enemies() -> Vec<(Entity, Vec3, f32, f32)> {
+    vec![
+        // Entity, position, distance left on the route, health
+        (Entity::from_raw(0), Vec3::new(1.0, 0.0, 0.0), 6.0, 10.0),
+        (Entity::from_raw(1), Vec3::new(8.0, 0.0, 0.0), 1.0, 2.0),
+        (Entity::from_raw(2), Vec3::new(3.0, 0.0, 5.0), 9.0, 30.0),
+    ]
+}
+
+fn pick(mode: TargetingMode) -> u32 {
+    mode.pick(Vec3::ZERO, enemies()).unwrap().0.index()
+}
+
+#[test]
+fn each_mode_picks_its_target() {
+    assert_eq!(pick(TargetingMode::First), 1);
+    assert_eq!(pick(TargetingMode::Last), 2);
+    assert_eq!(pick(TargetingMode::Strongest), 2);
+    assert_eq!(pick(TargetingMode::Weakest), 1);
+    assert_eq!(pick(TargetingMode::Closest), 0);
+}
+
+#[test]
+fn no_target_without_enemies() {
+    assert_eq!(TargetingMode::First.pick(Vec3::ZERO, vec![]), None);
+}
+
+#[test]
+fn first_targets_the_enemy_closest_to_the_end_of_its_route() {
+    let mut app = loaded_app();
+    let mut path = PathManager::new();
+    // A long route along x and a short one beside it
+    for (route_id, nodes, z) in [(0, 9, 0.0), (1, 2, 4.0)] {
+        for node_id in 0..nodes {
+            path.push(Proxy {
+                route_id,
+                node_id,
+                kind: ProxyKind::Route,
+                movement_type: MovementType::Walking,
+                location: Vec3::new(node_id as f32 * 5.0, 0.0, z),
+            });
+        }
+    }
+    app.world.spawn(path);
+    let mut wave = drones(1, 0.0);
+    wave.groups[0].route = Some(0);
+    let mut late = drones(1, 0.0);
+    late.groups[0].route = Some(1);
+    late.groups[0].delay = 1.0;
+    wave.groups.extend(late.groups);
+    set_waves(&mut app, vec![wave]);
+    app.update();
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.5);
+
+    // The enemy on the long route walked further but has more left to walk
+    let tower = Vec3::new(2.0, 0.0, 2.0);
+    let gun = build_tower(&mut app, "gun", tower);
+    app.world.send_event(TowerBuildEvent::SetTargeting {
+        entity: gun,
+        mode: TargetingMode::First,
+    });
+    let mut projectiles = app.world.query::<&Projectile>();
+    for _ in 0..100 {
+        app.update();
+        if let Some(projectile) = projectiles.iter(&app.world).next() {
+            // Aimed at the short route beside the tower
+            assert!(projectile.direction.unwrap().z > 0.0);
+            return;
+        }
+    }
+    panic!("the tower never shot");
+}
+
+fn projectiles_shot_by_tower_at(position: Vec3) -> usize {
+    let mut app = loaded_app();
+    set_waves(&mut app, vec![drones(3, 0.5)]);
+    spawn_straight_path(&mut app);
+    build_tower(&mut app, "gun", position);
+    app.world.send_event(StateUpdateEvent::StartWave);
+    run_for(&mut app, 1.0);
+    app.world
+        .query_filtered::<(), With<Projectile>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn towers_only_shoot_at_enemies_in_range() {
+    // The route runs from the origin to (10, 0, 0)
+    assert!(projectiles_shot_by_tower_at(Vec3::new(5.0, 0.0, 3.0)) > 0);
+    assert_eq!(projectiles_shot_by_tower_at(Vec3::new(5.0, 0.0, 40.0)), 0);
+}
+
+#[test]
+fn range_upgrades_extend_the_range() {
+    let mut app = loaded_app();
+    app.update();
+    let assets = app.world.resource::<GameAssets>();
+    let catalogs = app.world.resource::<Assets<TowerCatalog>>();
+    let gun = catalogs
+        .get(&assets.towers)
+        .unwrap()
+        .get(&TowerType("gun".to_string()))
+        .unwrap();
+    let upgrades = [
+        TowerUpgrades::RangeBuff(3.0),
+        TowerUpgrades::ForceBuff(5.0),
+        TowerUpgrades::RangeBuff(2.0),
+    ];
+    assert_eq!(gun.range(&upgrades), gun.range + 5.0);
+}